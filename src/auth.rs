@@ -0,0 +1,254 @@
+use std::sync::{Condvar, Mutex, RwLock};
+use rustc_serialize::base64;
+use rustc_serialize::base64::ToBase64;
+use hyper::header::{Authorization, Headers};
+pub use types::Error;
+
+// Adds the credentials to every request of the client. When the server answers 401, `refresh()` is called
+// and if it returns true the request is sent once more with the refreshed credentials.
+pub trait AuthProvider: Send + Sync {
+    fn apply(&self, headers: &mut Headers);
+
+    // Headers set by `apply()`, they are dropped when a redirect leads to another host.
+    fn header_names(&self) -> Vec<String> {
+        vec!["Authorization".to_string()]
+    }
+
+    fn refresh(&self) -> bool {
+        false
+    }
+}
+
+pub struct BasicAuth {
+    username: String,
+    password: String,
+}
+
+impl BasicAuth {
+    pub fn new(username: String, password: String) -> BasicAuth {
+        BasicAuth {
+            username: username,
+            password: password,
+        }
+    }
+}
+
+impl AuthProvider for BasicAuth {
+    fn apply(&self, headers: &mut Headers) {
+        let credential = format!("{}:{}", self.username, self.password).into_bytes()[..].to_base64(base64::STANDARD);
+        headers.set(Authorization(format!("Basic {}", credential)));
+    }
+}
+
+type TokenSource = Box<dyn Fn() -> Result<String, Error> + Send + Sync>;
+
+// Concurrent rejected requests wait for the running fetch instead of each calling the source.
+#[derive(Default)]
+struct FetchState {
+    running: bool,
+    // Number of finished fetches, tells the waiting requests that the running one is done.
+    finished: usize,
+    waiting: usize,
+    // Outcome of the last fetch, shared with the requests that waited for it.
+    succeeded: bool,
+}
+
+pub struct BearerAuth {
+    token: RwLock<Option<String>>,
+    source: Option<TokenSource>,
+    state: Mutex<FetchState>,
+    fetched: Condvar,
+}
+
+impl BearerAuth {
+    // A fixed token, a 401 is returned as it is.
+    pub fn new(token: String) -> BearerAuth {
+        BearerAuth {
+            token: RwLock::new(Some(token)),
+            source: None,
+            state: Mutex::new(FetchState::default()),
+            fetched: Condvar::new(),
+        }
+    }
+
+    // The token is fetched from the source on first use and again whenever the server rejects it.
+    pub fn from_source<F: Fn() -> Result<String, Error> + Send + Sync + 'static>(source: F) -> BearerAuth {
+        BearerAuth {
+            token: RwLock::new(None),
+            source: Some(Box::new(source)),
+            state: Mutex::new(FetchState::default()),
+            fetched: Condvar::new(),
+        }
+    }
+
+    // Calls the source, or waits for the outcome of the call already running.
+    fn fetch(&self) -> bool {
+        let source = match self.source {
+            Some(ref source) => source,
+            None => return false,
+        };
+
+        let mut state = self.state.lock().unwrap();
+        if state.running {
+            let finished = state.finished;
+            state.waiting += 1;
+            while state.finished == finished {
+                state = self.fetched.wait(state).unwrap();
+            }
+            state.waiting -= 1;
+            return state.succeeded;
+        }
+        state.running = true;
+        drop(state);
+
+        let succeeded = match source() {
+            Ok(token) => {
+                *self.token.write().unwrap() = Some(token);
+                true
+            },
+            Err(err) => {
+                error!("Bearer token could not be fetched: {}", err);
+                false
+            },
+        };
+
+        let mut state = self.state.lock().unwrap();
+        state.running = false;
+        state.finished += 1;
+        state.succeeded = succeeded;
+        self.fetched.notify_all();
+        succeeded
+    }
+}
+
+impl AuthProvider for BearerAuth {
+    fn apply(&self, headers: &mut Headers) {
+        if self.token.read().unwrap().is_none() {
+            self.fetch();
+        }
+        if let Some(ref token) = *self.token.read().unwrap() {
+            headers.set(Authorization(format!("Bearer {}", token)));
+        }
+    }
+
+    fn refresh(&self) -> bool {
+        self.fetch()
+    }
+}
+
+// Credentials sent in a custom header, eg. an API key expected by a proxy.
+pub struct HeaderAuth {
+    name: String,
+    value: String,
+}
+
+impl HeaderAuth {
+    pub fn new(name: String, value: String) -> HeaderAuth {
+        HeaderAuth {
+            name: name,
+            value: value,
+        }
+    }
+}
+
+impl AuthProvider for HeaderAuth {
+    fn apply(&self, headers: &mut Headers) {
+        headers.set_raw(self.name.clone(), vec![self.value.clone().into_bytes()]);
+    }
+
+    fn header_names(&self) -> Vec<String> {
+        vec![self.name.clone()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Condvar, Mutex};
+    use std::thread;
+    use hyper::header::{Authorization, Headers};
+    use auth::{AuthProvider, BasicAuth, BearerAuth, HeaderAuth};
+    use types::Error;
+
+    #[test]
+    fn test_basic_auth() {
+        let mut headers = Headers::new();
+        BasicAuth::new("neo4j".to_string(), "secret".to_string()).apply(&mut headers);
+        assert_eq!("Basic bmVvNGo6c2VjcmV0", headers.get::<Authorization<String>>().unwrap().0);
+        assert!(!BasicAuth::new("neo4j".to_string(), "secret".to_string()).refresh());
+    }
+
+    #[test]
+    fn test_bearer_auth() {
+        let mut headers = Headers::new();
+        let auth = BearerAuth::new("abc".to_string());
+        auth.apply(&mut headers);
+        assert_eq!("Bearer abc", headers.get::<Authorization<String>>().unwrap().0);
+        assert!(!auth.refresh());
+
+        let calls = Arc::new(Mutex::new(0));
+        let source_calls = calls.clone();
+        let auth = BearerAuth::from_source(move || {
+            let mut calls = source_calls.lock().unwrap();
+            *calls += 1;
+            match *calls {
+                3 => Err(Error::ConfigError("token service is down".to_string())),
+                n => Ok(format!("token-{}", n)),
+            }
+        });
+        auth.apply(&mut headers);
+        auth.apply(&mut headers);
+        assert_eq!("Bearer token-1", headers.get::<Authorization<String>>().unwrap().0);
+        assert!(auth.refresh());
+        auth.apply(&mut headers);
+        assert_eq!("Bearer token-2", headers.get::<Authorization<String>>().unwrap().0);
+        assert!(!auth.refresh());
+        assert_eq!(3, *calls.lock().unwrap());
+    }
+
+    #[test]
+    fn test_bearer_auth_concurrent_refreshes() {
+        let calls = Arc::new(Mutex::new(0));
+        let gate = Arc::new((Mutex::new(false), Condvar::new()));
+        let source_calls = calls.clone();
+        let source_gate = gate.clone();
+        let auth = Arc::new(BearerAuth::from_source(move || {
+            *source_calls.lock().unwrap() += 1;
+            let (ref open, ref opened) = *source_gate;
+            let mut open = open.lock().unwrap();
+            while !*open {
+                open = opened.wait(open).unwrap();
+            }
+            Ok("token".to_string())
+        }));
+
+        let threads: Vec<_> = (0..4).map(|_| {
+            let auth = auth.clone();
+            thread::spawn(move || auth.refresh())
+        }).collect();
+
+        // The source only returns once the three other requests wait for it.
+        loop {
+            {
+                let state = auth.state.lock().unwrap();
+                if state.running && state.waiting == 3 {
+                    break;
+                }
+            }
+            thread::yield_now();
+        }
+        *gate.0.lock().unwrap() = true;
+        gate.1.notify_all();
+
+        for thread in threads {
+            assert!(thread.join().unwrap());
+        }
+        assert_eq!(1, *calls.lock().unwrap());
+    }
+
+    #[test]
+    fn test_header_auth() {
+        let mut headers = Headers::new();
+        HeaderAuth::new("X-Api-Key".to_string(), "abc".to_string()).apply(&mut headers);
+        assert_eq!(Some("abc"), headers.get_raw("X-Api-Key").map(|raw| ::std::str::from_utf8(&raw[0]).unwrap()));
+    }
+}
@@ -0,0 +1,373 @@
+use std::io::Read;
+use rustc_serialize::{json, Encodable};
+use rustc_serialize::json::Json;
+use hyper;
+use url::percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
+use client::RequestBuilder;
+use service::ServiceRoot;
+use transport::Response;
+pub use types::Error;
+
+#[cfg(feature = "async")] use futures::future::{self, BoxFuture, FutureExt};
+
+// A node known by its id, or the node (or relationship, for set_property()) created by an earlier job of the batch.
+// Jobs are numbered from 0 in the order they are enqueued, referring to any other job is an IntegrityError.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NodeRef {
+    Id(u64),
+    Job(usize),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BatchResult {
+    Node(u64),
+    Relationship(u64),
+    // Label additions and property sets.
+    Done,
+}
+
+impl BatchResult {
+    pub fn id(&self) -> Option<u64> {
+        match *self {
+            BatchResult::Node(id) => Some(id),
+            BatchResult::Relationship(id) => Some(id),
+            BatchResult::Done => None,
+        }
+    }
+}
+
+enum BatchJob {
+    CreateNode(Json),
+    Connect {
+        from: NodeRef,
+        to: NodeRef,
+        type_name: String,
+        properties: Option<Json>,
+    },
+    AddLabels(NodeRef, Vec<String>),
+    SetProperty(NodeRef, String, Json),
+}
+
+#[derive(RustcEncodable)]
+struct BatchJobParam {
+    method: String,
+    to: String,
+    body: Json,
+    id: usize,
+}
+
+// Operations sent to /db/data/batch in one request and executed in one transaction: if one of them fails,
+// none of them is applied.
+pub struct BatchBuilder {
+    jobs: Vec<BatchJob>,
+    // The first invalid job, reported by execute().
+    error: Option<Error>,
+}
+
+impl Default for BatchBuilder {
+    fn default() -> BatchBuilder {
+        BatchBuilder::new()
+    }
+}
+
+impl BatchBuilder {
+    pub fn new() -> BatchBuilder {
+        BatchBuilder {
+            jobs: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn create_node<T: Encodable>(self, properties: Option<&T>) -> BatchBuilder {
+        let properties = match properties {
+            Some(properties) => encode(properties),
+            None => Ok(Json::Object(json::Object::new())),
+        };
+        match properties {
+            Ok(properties) => self.push(BatchJob::CreateNode(properties)),
+            Err(err) => self.fail(err),
+        }
+    }
+
+    pub fn connect<T: Encodable>(self, from: NodeRef, to: NodeRef, type_name: String, properties: Option<&T>) -> BatchBuilder {
+        let properties = match properties {
+            Some(properties) => match encode(properties) {
+                Ok(properties) => Some(properties),
+                Err(err) => return self.fail(err),
+            },
+            None => None,
+        };
+        if !self.is_valid(from, false) || !self.is_valid(to, false) {
+            return self.fail(Error::IntegrityError);
+        }
+        self.push(BatchJob::Connect {
+            from: from,
+            to: to,
+            type_name: type_name,
+            properties: properties,
+        })
+    }
+
+    pub fn add_labels(self, node: NodeRef, labels: Vec<String>) -> BatchBuilder {
+        if !self.is_valid(node, false) {
+            return self.fail(Error::IntegrityError);
+        }
+        self.push(BatchJob::AddLabels(node, labels))
+    }
+
+    pub fn set_property<V: Encodable>(self, node: NodeRef, key: String, value: V) -> BatchBuilder {
+        let value = match encode(&value) {
+            Ok(value) => value,
+            Err(err) => return self.fail(err),
+        };
+        if !self.is_valid(node, true) {
+            return self.fail(Error::IntegrityError);
+        }
+        self.push(BatchJob::SetProperty(node, key, value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    // The results follow the order of the jobs.
+    pub fn execute(mut self, cli: &::client::Client) -> Result<Vec<BatchResult>, Error> {
+        let req = match self.request(cli) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        let res = try_rest!(req, Ok);
+        self.results_from_response(res)
+    }
+
+    #[cfg(feature = "async")]
+    pub fn execute_async(mut self, cli: &::client::Client) -> BoxFuture<'static, Result<Vec<BatchResult>, Error>> {
+        let req = match self.request(cli) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(req, Ok)
+            .map(move |res| res.and_then(|res| self.results_from_response(res)))
+            .boxed()
+    }
+
+    fn push(mut self, job: BatchJob) -> BatchBuilder {
+        self.jobs.push(job);
+        self
+    }
+
+    fn fail(mut self, err: Error) -> BatchBuilder {
+        if self.error.is_none() {
+            self.error = Some(err);
+        }
+        self
+    }
+
+    // Jobs can only refer to the ones enqueued before them which create a node, or a relationship if allowed.
+    fn is_valid(&self, node: NodeRef, relationship: bool) -> bool {
+        match node {
+            NodeRef::Id(_) => true,
+            NodeRef::Job(job) => match self.jobs.get(job) {
+                Some(&BatchJob::CreateNode(_)) => true,
+                Some(&BatchJob::Connect { .. }) => relationship,
+                _ => false,
+            },
+        }
+    }
+
+    fn request<'c>(&mut self, cli: &'c ::client::Client) -> Result<RequestBuilder<'c>, Error> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        let root = match cli.service_root() {
+            Ok(root) => root,
+            Err(err) => return Err(err),
+        };
+
+        let params: Vec<BatchJobParam> = self.jobs.iter().enumerate().map(|(id, job)| {
+            let (method, to, body) = match *job {
+                BatchJob::CreateNode(ref properties) => ("POST", cli.build_uri(root.node.clone()), properties.clone()),
+                BatchJob::Connect { from, to, ref type_name, ref properties } => {
+                    let mut body = json::Object::new();
+                    body.insert("to".to_string(), Json::String(node_uri(cli, &root, to)));
+                    body.insert("type".to_string(), Json::String(type_name.clone()));
+                    if let Some(ref properties) = *properties {
+                        body.insert("data".to_string(), properties.clone());
+                    }
+                    ("POST", format!("{}/relationships", node_uri(cli, &root, from)), Json::Object(body))
+                },
+                BatchJob::AddLabels(node, ref labels) => {
+                    ("POST", format!("{}/labels", node_uri(cli, &root, node)), Json::Array(labels.iter().cloned().map(Json::String).collect()))
+                },
+                BatchJob::SetProperty(node, ref key, ref value) => {
+                    ("PUT", format!("{}/properties/{}", node_uri(cli, &root, node), utf8_percent_encode(key, PATH_SEGMENT_ENCODE_SET)), value.clone())
+                },
+            };
+            BatchJobParam {
+                method: method.to_string(),
+                to: to,
+                body: body,
+                id: id,
+            }
+        }).collect();
+
+        let payload = match json::encode(&params) {
+            Ok(s) => s,
+            Err(err) => return Err(Error::from(err)),
+        };
+        Ok(cli.post(cli.build_uri(root.batch.clone())).body(&payload))
+    }
+
+    // Each job result has the id of its job and the location of the created node or relationship.
+    fn results_from_response(&self, mut res: Response) -> Result<Vec<BatchResult>, Error> {
+        let mut res_raw = String::new();
+        let _ = res.read_to_string(&mut res_raw);
+        let results = match Json::from_str(&res_raw) {
+            Ok(Json::Array(results)) => results,
+            Ok(_) => return Err(Error::DataError(From::from(format!("Unexpected batch response: {}", res_raw)))),
+            Err(err) => return Err(Error::from(err)),
+        };
+
+        let locations: Vec<(usize, Option<u64>)> = results.iter().filter_map(|result| {
+            let id = match result.find("id").and_then(|id| id.as_u64()) {
+                Some(id) => id as usize,
+                None => return None,
+            };
+            let location = result.find("location")
+                .and_then(|location| location.as_string())
+                .and_then(|location| location.rsplit('/').next())
+                .and_then(|id| id.parse::<u64>().ok());
+            Some((id, location))
+        }).collect();
+
+        self.jobs.iter().enumerate().map(|(id, job)| {
+            let location = locations.iter().find(|&&(job_id, _)| job_id == id).and_then(|&(_, location)| location);
+            match (job, location) {
+                (&BatchJob::CreateNode(_), Some(location)) => Ok(BatchResult::Node(location)),
+                (&BatchJob::Connect { .. }, Some(location)) => Ok(BatchResult::Relationship(location)),
+                (&BatchJob::CreateNode(_), None) | (&BatchJob::Connect { .. }, None) => {
+                    Err(Error::DataError(From::from(format!("No location returned for batch job {}", id))))
+                },
+                _ => Ok(BatchResult::Done),
+            }
+        }).collect()
+    }
+}
+
+// Back-references are resolved by the server, eg. "{0}/relationships".
+fn node_uri(cli: &::client::Client, root: &ServiceRoot, node: NodeRef) -> String {
+    match node {
+        NodeRef::Id(id) => cli.build_uri(root.node_uri(id)),
+        NodeRef::Job(job) => format!("{{{}}}", job),
+    }
+}
+
+fn encode<T: Encodable>(value: &T) -> Result<Json, Error> {
+    let value_raw = match json::encode(value) {
+        Ok(s) => s,
+        Err(err) => return Err(Error::from(err)),
+    };
+    match Json::from_str(&value_raw) {
+        Ok(value) => Ok(value),
+        Err(err) => Err(Error::from(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use batch;
+    use batch::{BatchBuilder, BatchResult, NodeRef};
+    use client;
+    use hyper::method::Method;
+    use hyper::status::StatusCode;
+    use transport::MockTransport;
+
+    #[derive(RustcEncodable)]
+    struct TestNodeData {
+        name: String,
+    }
+
+    fn get_mock_client(transport: &MockTransport) -> ::client::Client {
+        let builder = client::ClientBuilder::new()
+            .discovery(false)
+            .transport(transport.clone());
+        #[cfg(feature = "async")]
+        let builder = builder.async_transport(transport.clone());
+        builder.get()
+    }
+
+    #[test]
+    pub fn test_batch_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Ok, r#"[
+            {"id":0,"location":"http://localhost:7474/db/data/node/17","body":{"metadata":{"id":17,"labels":[]},"data":{"name":"Steve"}},"from":"http://localhost:7474/db/data/node"},
+            {"id":1,"location":"http://localhost:7474/db/data/relationship/5","body":{},"from":"{0}/relationships"},
+            {"id":2,"from":"{0}/labels"},
+            {"id":3,"from":"{1}/properties/weight"}
+        ]"#);
+        let cli = get_mock_client(&transport);
+
+        let mut props = HashMap::new();
+        props.insert("since".to_string(), 2016);
+        let batch = BatchBuilder::new()
+            .create_node(Some(&TestNodeData { name: "Steve".to_string() }))
+            .connect(NodeRef::Job(0), NodeRef::Id(42), "Likes".to_string(), Some(&props))
+            .add_labels(NodeRef::Job(0), vec!["Person".to_string()])
+            .set_property(NodeRef::Job(1), "weight".to_string(), 3);
+        assert_eq!(4, batch.len());
+
+        let results = batch.execute(&cli).unwrap();
+        assert_eq!(vec![BatchResult::Node(17), BatchResult::Relationship(5), BatchResult::Done, BatchResult::Done], results);
+        assert_eq!(Some(17), results[0].id());
+
+        let requests = transport.requests();
+        assert_eq!(Method::Post, requests[0].method);
+        assert_eq!("http://localhost:7474/db/data/batch", requests[0].uri);
+        assert_eq!(Some(concat!(
+            r#"[{"method":"POST","to":"http://localhost:7474/db/data/node","body":{"name":"Steve"},"id":0},"#,
+            r#"{"method":"POST","to":"{0}/relationships","body":{"data":{"since":2016},"to":"http://localhost:7474/db/data/node/42","type":"Likes"},"id":1},"#,
+            r#"{"method":"POST","to":"{0}/labels","body":["Person"],"id":2},"#,
+            r#"{"method":"PUT","to":"{1}/properties/weight","body":3,"id":3}]"#).to_string()), requests[0].body);
+    }
+
+    #[test]
+    pub fn test_batch_invalid_job_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::InternalServerError, r#"{"message":"Node 42 not found","exception":"NotFoundException"}"#);
+        let cli = get_mock_client(&transport);
+
+        let batch = BatchBuilder::new()
+            .create_node::<TestNodeData>(None)
+            .add_labels(NodeRef::Job(1), vec!["Person".to_string()]);
+        assert!(match batch.execute(&cli) { Err(batch::Error::IntegrityError) => true, _ => false });
+
+        // Labels and relationships need a node, properties a node or a relationship.
+        let batch = BatchBuilder::new()
+            .create_node::<TestNodeData>(None)
+            .connect::<TestNodeData>(NodeRef::Job(0), NodeRef::Id(42), "Likes".to_string(), None)
+            .add_labels(NodeRef::Job(1), vec!["Person".to_string()]);
+        assert!(match batch.execute(&cli) { Err(batch::Error::IntegrityError) => true, _ => false });
+        let batch = BatchBuilder::default()
+            .create_node::<TestNodeData>(None)
+            .add_labels(NodeRef::Job(0), vec!["Person".to_string()])
+            .connect::<TestNodeData>(NodeRef::Id(42), NodeRef::Job(1), "Likes".to_string(), None);
+        assert!(match batch.execute(&cli) { Err(batch::Error::IntegrityError) => true, _ => false });
+        let batch = BatchBuilder::new()
+            .create_node::<TestNodeData>(None)
+            .set_property(NodeRef::Job(0), "name".to_string(), "Steve")
+            .set_property(NodeRef::Job(1), "name".to_string(), "Steve");
+        assert!(match batch.execute(&cli) { Err(batch::Error::IntegrityError) => true, _ => false });
+        assert!(transport.requests().is_empty());
+
+        let batch = BatchBuilder::new()
+            .connect::<TestNodeData>(NodeRef::Id(42), NodeRef::Id(43), "Likes".to_string(), None);
+        let err = batch.execute(&cli).unwrap_err();
+        assert_eq!(Some(StatusCode::InternalServerError), err.status());
+        assert_eq!("NotFoundException", err.neo4j_errors()[0].code);
+    }
+}
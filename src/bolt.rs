@@ -0,0 +1,850 @@
+use std::collections::BTreeMap;
+use std::io::{self, Cursor, Read, Write};
+use std::net::TcpStream;
+use std::sync::Mutex;
+use rustc_serialize::json::{self, Json};
+use hyper::status::StatusCode;
+use types::{Neo4jError, ResponseError};
+pub use types::Error;
+
+const BOLT_MAGIC: [u8; 4] = [0x60, 0x60, 0xB0, 0x17];
+// Bolt 4.4 down to 4.1, the messages used here are the same in all of them.
+const BOLT_VERSIONS: [[u8; 4]; 4] = [[0, 0, 4, 4], [0, 0, 3, 4], [0, 0, 2, 4], [0, 0, 1, 4]];
+const MAX_CHUNK_SIZE: usize = 0xFFFF;
+const USER_AGENT: &str = concat!("neo4j_client/", env!("CARGO_PKG_VERSION"));
+
+pub const HELLO: u8 = 0x01;
+pub const GOODBYE: u8 = 0x02;
+pub const RESET: u8 = 0x0F;
+pub const RUN: u8 = 0x10;
+pub const BEGIN: u8 = 0x11;
+pub const COMMIT: u8 = 0x12;
+pub const ROLLBACK: u8 = 0x13;
+pub const PULL: u8 = 0x3F;
+pub const SUCCESS: u8 = 0x70;
+pub const RECORD: u8 = 0x71;
+pub const IGNORED: u8 = 0x7E;
+pub const FAILURE: u8 = 0x7F;
+
+const NODE: u8 = 0x4E;
+const RELATIONSHIP: u8 = 0x52;
+
+// A PackStream value, messages are structures with the message type as signature.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+    Structure(u8, Vec<Value>),
+}
+
+impl Value {
+    pub fn from_json(json: &Json) -> Value {
+        match *json {
+            Json::Null => Value::Null,
+            Json::Boolean(b) => Value::Boolean(b),
+            Json::I64(i) => Value::Integer(i),
+            Json::U64(u) => Value::Integer(u as i64),
+            Json::F64(f) => Value::Float(f),
+            Json::String(ref s) => Value::String(s.clone()),
+            Json::Array(ref items) => Value::List(items.iter().map(Value::from_json).collect()),
+            Json::Object(ref map) => Value::Map(map.iter().map(|(k, v)| (k.clone(), Value::from_json(v))).collect()),
+        }
+    }
+
+    // Nodes and relationships are represented by their properties, the same way the REST endpoints return them.
+    pub fn to_json(&self) -> Json {
+        match *self {
+            Value::Null => Json::Null,
+            Value::Boolean(b) => Json::Boolean(b),
+            Value::Integer(i) if i >= 0 => Json::U64(i as u64),
+            Value::Integer(i) => Json::I64(i),
+            Value::Float(f) => Json::F64(f),
+            Value::String(ref s) => Json::String(s.clone()),
+            Value::Bytes(ref bytes) => Json::Array(bytes.iter().map(|b| Json::U64(*b as u64)).collect()),
+            Value::List(ref items) => Json::Array(items.iter().map(Value::to_json).collect()),
+            Value::Map(ref map) => Json::Object(map.iter().map(|(k, v)| (k.clone(), v.to_json())).collect()),
+            Value::Structure(NODE, ref fields) if fields.len() >= 3 => fields[2].to_json(),
+            Value::Structure(RELATIONSHIP, ref fields) if fields.len() >= 5 => fields[4].to_json(),
+            Value::Structure(_, ref fields) => Json::Array(fields.iter().map(Value::to_json).collect()),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match *self {
+            Value::Map(ref map) => map.get(key),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Value::String(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match *self {
+            Value::Null => buf.push(0xC0),
+            Value::Boolean(false) => buf.push(0xC2),
+            Value::Boolean(true) => buf.push(0xC3),
+            Value::Integer(i) => {
+                if (-16..=127).contains(&i) {
+                    buf.push(i as u8);
+                } else if (-128..=127).contains(&i) {
+                    buf.push(0xC8);
+                    buf.push(i as u8);
+                } else if (-32_768..=32_767).contains(&i) {
+                    buf.push(0xC9);
+                    buf.extend_from_slice(&(i as i16).to_be_bytes());
+                } else if (-2_147_483_648..=2_147_483_647).contains(&i) {
+                    buf.push(0xCA);
+                    buf.extend_from_slice(&(i as i32).to_be_bytes());
+                } else {
+                    buf.push(0xCB);
+                    buf.extend_from_slice(&i.to_be_bytes());
+                }
+            },
+            Value::Float(f) => {
+                buf.push(0xC1);
+                buf.extend_from_slice(&f.to_bits().to_be_bytes());
+            },
+            Value::String(ref s) => {
+                encode_size(buf, s.len(), Some(0x80), 0xD0);
+                buf.extend_from_slice(s.as_bytes());
+            },
+            Value::Bytes(ref bytes) => {
+                encode_size(buf, bytes.len(), None, 0xCC);
+                buf.extend_from_slice(bytes);
+            },
+            Value::List(ref items) => {
+                encode_size(buf, items.len(), Some(0x90), 0xD4);
+                for item in items {
+                    item.encode(buf);
+                }
+            },
+            Value::Map(ref map) => {
+                encode_size(buf, map.len(), Some(0xA0), 0xD8);
+                for (key, value) in map {
+                    Value::String(key.clone()).encode(buf);
+                    value.encode(buf);
+                }
+            },
+            Value::Structure(signature, ref fields) => {
+                buf.push(0xB0 + fields.len() as u8);
+                buf.push(signature);
+                for field in fields {
+                    field.encode(buf);
+                }
+            },
+        }
+    }
+
+    pub fn decode<R: Read>(reader: &mut R) -> Result<Value, Error> {
+        let marker = match read_bytes(reader, 1) {
+            Ok(bytes) => bytes[0],
+            Err(err) => return Err(err),
+        };
+
+        match marker {
+            0x00..=0x7F => Ok(Value::Integer(marker as i64)),
+            0xF0..=0xFF => Ok(Value::Integer(marker as i8 as i64)),
+            0xC0 => Ok(Value::Null),
+            0xC2 => Ok(Value::Boolean(false)),
+            0xC3 => Ok(Value::Boolean(true)),
+            0xC8..=0xCB => match read_bytes(reader, 1 << (marker - 0xC8)) {
+                Ok(bytes) => Ok(Value::Integer(bytes.iter().skip(1).fold(bytes[0] as i8 as i64, |acc, b| (acc << 8) | *b as i64))),
+                Err(err) => Err(err),
+            },
+            0xC1 => match read_bytes(reader, 8) {
+                Ok(bytes) => Ok(Value::Float(f64::from_bits(bytes.iter().fold(0, |acc, b| (acc << 8) | *b as u64)))),
+                Err(err) => Err(err),
+            },
+            0x80..=0x8F | 0xD0..=0xD2 => {
+                let bytes = match decode_size(reader, marker, 0x80, 0xD0).and_then(|size| read_bytes(reader, size)) {
+                    Ok(bytes) => bytes,
+                    Err(err) => return Err(err),
+                };
+                match String::from_utf8(bytes) {
+                    Ok(s) => Ok(Value::String(s)),
+                    Err(err) => Err(Error::DataError(Box::new(err))),
+                }
+            },
+            0xCC..=0xCE => match decode_size(reader, marker, 0xCC, 0xCC).and_then(|size| read_bytes(reader, size)) {
+                Ok(bytes) => Ok(Value::Bytes(bytes)),
+                Err(err) => Err(err),
+            },
+            0x90..=0x9F | 0xD4..=0xD6 => {
+                let size = match decode_size(reader, marker, 0x90, 0xD4) {
+                    Ok(size) => size,
+                    Err(err) => return Err(err),
+                };
+                let mut items = Vec::with_capacity(size);
+                for _ in 0..size {
+                    match Value::decode(reader) {
+                        Ok(item) => items.push(item),
+                        Err(err) => return Err(err),
+                    }
+                }
+                Ok(Value::List(items))
+            },
+            0xA0..=0xAF | 0xD8..=0xDA => {
+                let size = match decode_size(reader, marker, 0xA0, 0xD8) {
+                    Ok(size) => size,
+                    Err(err) => return Err(err),
+                };
+                let mut map = BTreeMap::new();
+                for _ in 0..size {
+                    let key = match Value::decode(reader) {
+                        Ok(Value::String(key)) => key,
+                        Ok(_) => return Err(invalid_data("PackStream map key is not a string")),
+                        Err(err) => return Err(err),
+                    };
+                    match Value::decode(reader) {
+                        Ok(value) => map.insert(key, value),
+                        Err(err) => return Err(err),
+                    };
+                }
+                Ok(Value::Map(map))
+            },
+            0xB0..=0xBF => {
+                let signature = match read_bytes(reader, 1) {
+                    Ok(bytes) => bytes[0],
+                    Err(err) => return Err(err),
+                };
+                let mut fields = Vec::new();
+                for _ in 0..(marker - 0xB0) {
+                    match Value::decode(reader) {
+                        Ok(field) => fields.push(field),
+                        Err(err) => return Err(err),
+                    }
+                }
+                Ok(Value::Structure(signature, fields))
+            },
+            _ => Err(invalid_data(&format!("Unknown PackStream marker: {:#X}", marker))),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for Value {
+    fn from(s: &'a str) -> Value {
+        Value::String(s.to_string())
+    }
+}
+
+fn encode_size(buf: &mut Vec<u8>, size: usize, tiny_marker: Option<u8>, marker: u8) {
+    match tiny_marker {
+        Some(tiny_marker) if size < 16 => buf.push(tiny_marker + size as u8),
+        _ if size <= 0xFF => {
+            buf.push(marker);
+            buf.push(size as u8);
+        },
+        _ if size <= 0xFFFF => {
+            buf.push(marker + 1);
+            buf.extend_from_slice(&(size as u16).to_be_bytes());
+        },
+        _ => {
+            buf.push(marker + 2);
+            buf.extend_from_slice(&(size as u32).to_be_bytes());
+        },
+    }
+}
+
+fn decode_size<R: Read>(reader: &mut R, marker: u8, tiny_marker: u8, size_marker: u8) -> Result<usize, Error> {
+    if marker < size_marker {
+        return Ok((marker - tiny_marker) as usize);
+    }
+    match read_bytes(reader, 1 << (marker - size_marker)) {
+        Ok(bytes) => Ok(bytes.iter().fold(0, |acc, b| (acc << 8) | *b as usize)),
+        Err(err) => Err(err),
+    }
+}
+
+fn read_bytes<R: Read>(reader: &mut R, size: usize) -> Result<Vec<u8>, Error> {
+    let mut bytes = vec![0; size];
+    match reader.read_exact(&mut bytes) {
+        Ok(_) => Ok(bytes),
+        Err(err) => Err(Error::from(err)),
+    }
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::DataError(Box::new(io::Error::new(io::ErrorKind::InvalidData, message.to_string())))
+}
+
+pub fn write_message<W: Write>(writer: &mut W, message: &Value) -> Result<(), Error> {
+    let mut data = Vec::new();
+    message.encode(&mut data);
+
+    let mut buf = Vec::with_capacity(data.len() + 4);
+    for chunk in data.chunks(MAX_CHUNK_SIZE) {
+        buf.extend_from_slice(&(chunk.len() as u16).to_be_bytes());
+        buf.extend_from_slice(chunk);
+    }
+    buf.extend_from_slice(&[0, 0]);
+
+    match writer.write_all(&buf).and_then(|_| writer.flush()) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(Error::from(err)),
+    }
+}
+
+pub fn read_message<R: Read>(reader: &mut R) -> Result<Value, Error> {
+    let mut data = Vec::new();
+    loop {
+        let size = match read_bytes(reader, 2) {
+            Ok(bytes) => ((bytes[0] as usize) << 8) | bytes[1] as usize,
+            Err(err) => return Err(err),
+        };
+        if size == 0 {
+            if data.is_empty() {
+                // NOOP chunk, sent by the server to keep the connection alive.
+                continue;
+            }
+            break;
+        }
+        match read_bytes(reader, size) {
+            Ok(chunk) => data.extend(chunk),
+            Err(err) => return Err(err),
+        }
+    }
+
+    Value::decode(&mut Cursor::new(data))
+}
+
+pub fn message(signature: u8, fields: Vec<Value>) -> Value {
+    Value::Structure(signature, fields)
+}
+
+pub fn map(entries: Vec<(&str, Value)>) -> Value {
+    Value::Map(entries.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+pub struct BoltResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+pub struct BoltConnection {
+    stream: TcpStream,
+    server: Option<String>,
+    // Set after an I/O or protocol error, such a connection is not given back to the pool.
+    broken: bool,
+}
+
+impl BoltConnection {
+    pub fn connect(address: &str, credentials: Option<&(String, String)>) -> Result<BoltConnection, Error> {
+        let mut stream = match TcpStream::connect(address) {
+            Ok(stream) => stream,
+            Err(err) => return Err(Error::from_connect_failure(err)),
+        };
+
+        let mut handshake = BOLT_MAGIC.to_vec();
+        for version in BOLT_VERSIONS.iter() {
+            handshake.extend_from_slice(version);
+        }
+        if let Err(err) = stream.write_all(&handshake) {
+            return Err(Error::from(err));
+        }
+        let version = match read_bytes(&mut stream, 4) {
+            Ok(version) => version,
+            Err(err) => return Err(err),
+        };
+        if version == [0, 0, 0, 0] {
+            return Err(invalid_data("The server does not support Bolt 4"));
+        }
+
+        let mut conn = BoltConnection {
+            stream: stream,
+            server: None,
+            broken: false,
+        };
+
+        let auth = match credentials {
+            Some((username, password)) => vec![
+                ("scheme", Value::from("basic")),
+                ("principal", Value::from(&**username)),
+                ("credentials", Value::from(&**password)),
+            ],
+            None => vec![("scheme", Value::from("none"))],
+        };
+        let mut hello = vec![("user_agent", Value::from(USER_AGENT))];
+        hello.extend(auth);
+
+        let metadata = match conn.request(message(HELLO, vec![map(hello)])) {
+            Ok(metadata) => metadata,
+            Err(err) => return Err(err),
+        };
+        conn.server = metadata.get("server").and_then(Value::as_str).map(str::to_string);
+
+        info!("Bolt connection established to {} (version {}.{})", address, version[3], version[2]);
+        Ok(conn)
+    }
+
+    pub fn get_server(&self) -> Option<&String> {
+        self.server.as_ref()
+    }
+
+    pub fn is_broken(&self) -> bool {
+        self.broken
+    }
+
+    // Runs the statement and pulls all of its records. On a failure the connection is reset, which also
+    // ends the enclosing transaction.
+    pub fn query(&mut self, statement: &str, parameters: Value) -> Result<BoltResult, Error> {
+        let parameters = match parameters {
+            Value::Null => Value::Map(BTreeMap::new()),
+            parameters => parameters,
+        };
+        let run = message(RUN, vec![Value::from(statement), parameters, Value::Map(BTreeMap::new())]);
+        let pull = message(PULL, vec![map(vec![("n", Value::Integer(-1))])]);
+        if let Err(err) = self.send(&run).and_then(|_| self.send(&pull)) {
+            return Err(err);
+        }
+
+        let columns = match self.receive_summary() {
+            Ok(metadata) => match metadata.get("fields") {
+                Some(&Value::List(ref fields)) => fields.iter().filter_map(Value::as_str).map(str::to_string).collect(),
+                _ => Vec::new(),
+            },
+            Err(err) => {
+                // The PULL has been ignored by the server.
+                let _ = self.receive();
+                return Err(self.recover(err));
+            },
+        };
+
+        let mut rows = Vec::new();
+        loop {
+            match self.receive() {
+                Ok(Value::Structure(RECORD, mut fields)) => match fields.pop() {
+                    Some(Value::List(row)) => rows.push(row),
+                    _ => return Err(self.protocol_error("Malformed RECORD message")),
+                },
+                Ok(summary) => match self.check_summary(summary) {
+                    Ok(_) => break,
+                    Err(err) => return Err(self.recover(err)),
+                },
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(BoltResult {
+            columns: columns,
+            rows: rows,
+        })
+    }
+
+    pub fn begin(&mut self) -> Result<(), Error> {
+        self.request_and_recover(message(BEGIN, vec![Value::Map(BTreeMap::new())]))
+    }
+
+    pub fn commit(&mut self) -> Result<(), Error> {
+        self.request_and_recover(message(COMMIT, vec![]))
+    }
+
+    pub fn rollback(&mut self) -> Result<(), Error> {
+        self.request_and_recover(message(ROLLBACK, vec![]))
+    }
+
+    pub fn reset(&mut self) -> Result<(), Error> {
+        self.request(message(RESET, vec![])).map(|_| ())
+    }
+
+    fn request_and_recover(&mut self, request: Value) -> Result<(), Error> {
+        match self.request(request) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(self.recover(err)),
+        }
+    }
+
+    fn request(&mut self, request: Value) -> Result<BTreeMap<String, Value>, Error> {
+        match self.send(&request) {
+            Ok(_) => self.receive_summary(),
+            Err(err) => Err(err),
+        }
+    }
+
+    // After a FAILURE the server ignores everything until a RESET.
+    fn recover(&mut self, err: Error) -> Error {
+        if let Error::ResponseError(_) = err {
+            if let Err(reset_err) = self.reset() {
+                warn!("Bolt connection could not be reset: {}", reset_err);
+                self.broken = true;
+            }
+        }
+        err
+    }
+
+    fn send(&mut self, request: &Value) -> Result<(), Error> {
+        let res = write_message(&mut self.stream, request);
+        if res.is_err() {
+            self.broken = true;
+        }
+        res
+    }
+
+    fn receive(&mut self) -> Result<Value, Error> {
+        let res = read_message(&mut self.stream);
+        if res.is_err() {
+            self.broken = true;
+        }
+        res
+    }
+
+    fn receive_summary(&mut self) -> Result<BTreeMap<String, Value>, Error> {
+        match self.receive() {
+            Ok(summary) => self.check_summary(summary),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn check_summary(&mut self, summary: Value) -> Result<BTreeMap<String, Value>, Error> {
+        match summary {
+            Value::Structure(SUCCESS, mut fields) => match fields.pop() {
+                Some(Value::Map(metadata)) => Ok(metadata),
+                _ => Ok(BTreeMap::new()),
+            },
+            Value::Structure(FAILURE, mut fields) => Err(failure_to_error(fields.pop().unwrap_or(Value::Null))),
+            Value::Structure(IGNORED, _) => Err(Error::ResponseError(ResponseError {
+                status: StatusCode::Ok,
+                body: String::new(),
+                errors: vec![Neo4jError {
+                    code: "Neo.ClientError.Request.Invalid".to_string(),
+                    message: "The request has been ignored by the server".to_string(),
+                }],
+            })),
+            _ => Err(self.protocol_error("Unexpected Bolt message")),
+        }
+    }
+
+    fn protocol_error(&mut self, message: &str) -> Error {
+        self.broken = true;
+        invalid_data(message)
+    }
+}
+
+impl Drop for BoltConnection {
+    fn drop(&mut self) {
+        if !self.broken {
+            let _ = write_message(&mut self.stream, &message(GOODBYE, vec![]));
+        }
+    }
+}
+
+// FAILURE metadata carries the same code and message as the errors of the REST endpoints.
+fn failure_to_error(metadata: Value) -> Error {
+    let code = metadata.get("code").and_then(Value::as_str).unwrap_or("").to_string();
+    let message = metadata.get("message").and_then(Value::as_str).unwrap_or("").to_string();
+    Error::from(ResponseError {
+        status: StatusCode::Ok,
+        body: json::encode(&metadata.to_json()).unwrap_or_default(),
+        errors: vec![Neo4jError {
+            code: code,
+            message: message,
+        }],
+    })
+}
+
+// Idle connections are kept for reuse, a connection is only taken by one statement or transaction at a time.
+pub struct BoltPool {
+    address: String,
+    credentials: Option<(String, String)>,
+    idle: Mutex<Vec<BoltConnection>>,
+}
+
+impl BoltPool {
+    pub fn new(address: String, credentials: Option<(String, String)>) -> BoltPool {
+        BoltPool {
+            address: address,
+            credentials: credentials,
+            idle: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn get_address(&self) -> &String {
+        &self.address
+    }
+
+    // Idle connections may have been closed by the server in the meantime, each one is checked with a RESET
+    // before it is handed out and dropped if that fails.
+    pub fn acquire(&self) -> Result<BoltConnection, Error> {
+        loop {
+            let idle = self.idle.lock().unwrap().pop();
+            let mut conn = match idle {
+                Some(conn) => conn,
+                None => break,
+            };
+            match conn.reset() {
+                Ok(_) => return Ok(conn),
+                Err(err) => {
+                    debug!("Dropping idle Bolt connection: {}", err);
+                    conn.broken = true;
+                },
+            }
+        }
+        BoltConnection::connect(&self.address, self.credentials.as_ref())
+    }
+
+    pub fn release(&self, conn: BoltConnection) {
+        if !conn.is_broken() {
+            self.idle.lock().unwrap().push(conn);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod stub {
+    use std::collections::VecDeque;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use bolt::{self, Value};
+
+    // In-process Bolt server for the tests: accepts connections one after the other and answers every
+    // received message with the next scripted batch of responses.
+    pub struct StubServer {
+        address: String,
+        received: Arc<Mutex<Vec<Value>>>,
+    }
+
+    impl StubServer {
+        pub fn start(script: Vec<Vec<Value>>) -> StubServer {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap().to_string();
+            let received = Arc::new(Mutex::new(Vec::new()));
+            let mut script: VecDeque<Vec<Value>> = script.into_iter().collect();
+
+            let received_by_server = received.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let mut stream = match stream {
+                        Ok(stream) => stream,
+                        Err(_) => return,
+                    };
+                    let mut handshake = [0; 20];
+                    if stream.read_exact(&mut handshake).is_err() || stream.write_all(&[0, 0, 4, 4]).is_err() {
+                        continue;
+                    }
+
+                    loop {
+                        let message = match bolt::read_message(&mut stream) {
+                            Ok(message) => message,
+                            Err(_) => break,
+                        };
+                        let is_goodbye = message == bolt::message(bolt::GOODBYE, vec![]);
+                        received_by_server.lock().unwrap().push(message);
+                        if is_goodbye {
+                            break;
+                        }
+                        for response in script.pop_front().unwrap_or(Vec::new()) {
+                            let _ = bolt::write_message(&mut stream, &response);
+                        }
+                    }
+                }
+            });
+
+            StubServer {
+                address: address,
+                received: received,
+            }
+        }
+
+        pub fn get_address(&self) -> String {
+            self.address.clone()
+        }
+
+        pub fn received(&self) -> Vec<Value> {
+            self.received.lock().unwrap().clone()
+        }
+
+        pub fn received_signatures(&self) -> Vec<u8> {
+            self.received().iter().filter_map(|message| match *message {
+                Value::Structure(signature, _) => Some(signature),
+                _ => None,
+            }).collect()
+        }
+    }
+
+    pub fn success(metadata: Vec<(&str, Value)>) -> Value {
+        bolt::message(bolt::SUCCESS, vec![bolt::map(metadata)])
+    }
+
+    pub fn record(row: Vec<Value>) -> Value {
+        bolt::message(bolt::RECORD, vec![Value::List(row)])
+    }
+
+    pub fn failure(code: &str, message: &str) -> Value {
+        bolt::message(bolt::FAILURE, vec![bolt::map(vec![("code", Value::from(code)), ("message", Value::from(message))])])
+    }
+
+    pub fn ignored() -> Value {
+        bolt::message(bolt::IGNORED, vec![])
+    }
+
+    pub fn hello() -> Vec<Value> {
+        vec![success(vec![("server", Value::from("Neo4j/4.4.0")), ("connection_id", Value::from("bolt-1"))])]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::io::{Cursor, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+    use rustc_serialize::json::Json;
+    use bolt::{self, BoltConnection, BoltPool, Value};
+    use bolt::stub::{self, StubServer};
+    use types::Classification;
+
+    fn roundtrip(value: Value) -> Value {
+        let mut buf = Vec::new();
+        value.encode(&mut buf);
+        Value::decode(&mut Cursor::new(buf)).unwrap()
+    }
+
+    #[test]
+    fn test_packstream_roundtrip() {
+        for i in vec![0, 1, -1, -16, -17, 127, 128, -128, -129, 32_767, -32_768, 32_768, 2_147_483_647, -2_147_483_649, i64::max_value(), i64::min_value()] {
+            assert_eq!(Value::Integer(i), roundtrip(Value::Integer(i)));
+        }
+        assert_eq!(Value::Float(-1.5), roundtrip(Value::Float(-1.5)));
+        assert_eq!(Value::Null, roundtrip(Value::Null));
+        assert_eq!(Value::Boolean(true), roundtrip(Value::Boolean(true)));
+
+        let long_string: String = (0..300).map(|_| "a").collect();
+        assert_eq!(Value::String(long_string.clone()), roundtrip(Value::String(long_string)));
+        assert_eq!(Value::from("Bolt"), roundtrip(Value::from("Bolt")));
+
+        let list = Value::List((0..20).map(Value::Integer).collect());
+        assert_eq!(list, roundtrip(list.clone()));
+        let nested = bolt::message(bolt::RUN, vec![Value::from("RETURN $x"), bolt::map(vec![("x", list)]), Value::Map(BTreeMap::new())]);
+        assert_eq!(nested, roundtrip(nested.clone()));
+    }
+
+    #[test]
+    fn test_packstream_encoding() {
+        let mut buf = Vec::new();
+        bolt::map(vec![("a", Value::Integer(1))]).encode(&mut buf);
+        assert_eq!(vec![0xA1, 0x81, 0x61, 0x01], buf);
+
+        let mut buf = Vec::new();
+        Value::Integer(-17).encode(&mut buf);
+        assert_eq!(vec![0xC8, 0xEF], buf);
+    }
+
+    #[test]
+    fn test_messages_are_chunked() {
+        let message = bolt::message(bolt::RUN, vec![Value::String((0..70_000).map(|_| "x").collect())]);
+        let mut buf = Vec::new();
+        bolt::write_message(&mut buf, &message).unwrap();
+        assert_eq!(vec![0xFF, 0xFF], buf[0..2].to_vec());
+        assert_eq!(vec![0, 0], buf[buf.len() - 2..].to_vec());
+        assert_eq!(message, bolt::read_message(&mut Cursor::new(buf)).unwrap());
+    }
+
+    #[test]
+    fn test_json_conversion() {
+        let json = Json::from_str(r#"{"name":"Steve","ids":[1,-2],"score":1.5,"admin":false,"boss":null}"#).unwrap();
+        let value = Value::from_json(&json);
+        assert_eq!(Some(&Value::Integer(-2)), match value.get("ids") {
+            Some(&Value::List(ref ids)) => ids.get(1),
+            _ => None,
+        });
+        assert_eq!(json, value.to_json());
+
+        let node = Value::Structure(0x4E, vec![Value::Integer(1), Value::List(vec![]), bolt::map(vec![("name", Value::from("Steve"))])]);
+        assert_eq!(Json::from_str(r#"{"name":"Steve"}"#).unwrap(), node.to_json());
+    }
+
+    #[test]
+    fn test_connection_query_with_mock_server() {
+        let server = StubServer::start(vec![
+            stub::hello(),
+            vec![stub::success(vec![("fields", Value::List(vec![Value::from("n.name")]))])],
+            vec![stub::record(vec![Value::from("Steve")]), stub::record(vec![Value::from("John")]), stub::success(vec![])],
+        ]);
+
+        let credentials = ("neo4j".to_string(), "secret".to_string());
+        let mut conn = BoltConnection::connect(&server.get_address(), Some(&credentials)).unwrap();
+        assert_eq!(Some(&"Neo4j/4.4.0".to_string()), conn.get_server());
+
+        let res = conn.query("MATCH (n) RETURN n.name", Value::Null).unwrap();
+        assert_eq!(vec!["n.name".to_string()], res.columns);
+        assert_eq!(vec![vec![Value::from("Steve")], vec![Value::from("John")]], res.rows);
+
+        let received = server.received();
+        assert_eq!(vec![bolt::HELLO, bolt::RUN, bolt::PULL], server.received_signatures());
+        match received[0] {
+            Value::Structure(_, ref fields) => {
+                assert_eq!(Some("basic"), fields[0].get("scheme").and_then(Value::as_str));
+                assert_eq!(Some("neo4j"), fields[0].get("principal").and_then(Value::as_str));
+                assert_eq!(Some("secret"), fields[0].get("credentials").and_then(Value::as_str));
+            },
+            _ => panic!("Expected HELLO"),
+        }
+    }
+
+    #[test]
+    fn test_pool_drops_closed_connections_with_mock_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let (closed, is_closed) = mpsc::channel();
+        // Accepts one connection and goes away after the HELLO, like a server shutting down.
+        thread::spawn(move || {
+            let mut stream = listener.accept().unwrap().0;
+            let mut handshake = [0; 20];
+            stream.read_exact(&mut handshake).unwrap();
+            stream.write_all(&[0, 0, 4, 4]).unwrap();
+            bolt::read_message(&mut stream).unwrap();
+            for response in stub::hello() {
+                bolt::write_message(&mut stream, &response).unwrap();
+            }
+            drop(stream);
+            drop(listener);
+            closed.send(()).unwrap();
+        });
+
+        let pool = BoltPool::new(address, None);
+        let conn = pool.acquire().unwrap();
+        pool.release(conn);
+        is_closed.recv().unwrap();
+
+        // The idle connection fails its RESET and a new one is refused.
+        assert!(pool.acquire().is_err());
+        assert!(pool.idle.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_connection_failure_resets_with_mock_server() {
+        let server = StubServer::start(vec![
+            stub::hello(),
+            vec![stub::failure("Neo.ClientError.Statement.SyntaxError", "Invalid input")],
+            vec![stub::ignored()],
+            vec![stub::success(vec![])],
+        ]);
+
+        let pool = BoltPool::new(server.get_address(), None);
+        let mut conn = pool.acquire().unwrap();
+        let err = match conn.query("MATCH (n RETURN n", Value::Null) {
+            Err(err) => err,
+            Ok(_) => panic!("Expected query error"),
+        };
+        assert!(err.has_neo4j_status(Classification::ClientError, "Statement", "SyntaxError"));
+        assert!(!conn.is_broken());
+        assert_eq!(vec![bolt::HELLO, bolt::RUN, bolt::PULL, bolt::RESET], server.received_signatures());
+        match server.received()[0] {
+            Value::Structure(_, ref fields) => assert_eq!(Some("none"), fields[0].get("scheme").and_then(Value::as_str)),
+            _ => panic!("Expected HELLO"),
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+use rustc_serialize::{json, Decodable, Encodable};
+use rustc_serialize::json::Json;
+pub use types::Error;
+
+// Local changes of a node or relationship compared to the last known server state, see `Snapshot::diff()`.
+#[derive(Debug, Default, PartialEq)]
+pub struct Changes {
+    pub set_properties: BTreeMap<String, Json>,
+    pub removed_properties: Vec<String>,
+    pub added_labels: Vec<String>,
+    pub removed_labels: Vec<String>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.set_properties.is_empty() && self.removed_properties.is_empty() && self.added_labels.is_empty() && self.removed_labels.is_empty()
+    }
+}
+
+// The properties and labels as they were last seen on the server. Null properties are not stored by Neo4j, they count as missing.
+#[derive(Debug, Default, Clone)]
+pub struct Snapshot {
+    properties: BTreeMap<String, Json>,
+    labels: Vec<String>,
+}
+
+impl Snapshot {
+    pub fn new() -> Snapshot {
+        Snapshot::default()
+    }
+
+    pub fn take<T: Encodable>(properties: &Option<T>, labels: &[String]) -> Result<Snapshot, Error> {
+        match encode_properties(properties) {
+            Ok(properties) => Ok(Snapshot {
+                properties: properties,
+                labels: labels.to_vec(),
+            }),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn diff<T: Encodable>(&self, properties: &Option<T>, labels: &[String]) -> Result<Changes, Error> {
+        let properties = match encode_properties(properties) {
+            Ok(properties) => properties,
+            Err(err) => return Err(err),
+        };
+
+        let mut changes = Changes::default();
+        for (key, value) in &properties {
+            if self.properties.get(key) != Some(value) {
+                changes.set_properties.insert(key.clone(), value.clone());
+            }
+        }
+        changes.removed_properties = self.properties.keys().filter(|key| !properties.contains_key(*key)).cloned().collect();
+        changes.added_labels = labels.iter().filter(|label| !self.labels.contains(label)).cloned().collect();
+        changes.removed_labels = self.labels.iter().filter(|label| !labels.contains(label)).cloned().collect();
+        Ok(changes)
+    }
+
+    pub fn set_property(&mut self, key: String, value: Json) {
+        if value == Json::Null {
+            self.properties.remove(&key);
+        } else {
+            self.properties.insert(key, value);
+        }
+    }
+
+    // The properties as they were just written to the server, the labels are kept.
+    pub fn set_properties<T: Encodable>(&mut self, properties: &Option<T>) -> Result<(), Error> {
+        match encode_properties(properties) {
+            Ok(properties) => {
+                self.properties = properties;
+                Ok(())
+            },
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn remove_property(&mut self, key: &str) {
+        self.properties.remove(key);
+    }
+
+    pub fn clear_properties(&mut self) {
+        self.properties.clear();
+    }
+
+    pub fn add_label(&mut self, label: String) {
+        if !self.labels.contains(&label) {
+            self.labels.push(label);
+        }
+    }
+
+    pub fn remove_label(&mut self, label: &str) {
+        self.labels.retain(|l| l != label);
+    }
+
+    pub fn set_labels(&mut self, labels: Vec<String>) {
+        self.labels = labels;
+    }
+
+    pub fn apply(&mut self, changes: Changes) {
+        for (key, value) in changes.set_properties {
+            self.set_property(key, value);
+        }
+        for key in changes.removed_properties {
+            self.remove_property(&key);
+        }
+        for label in changes.added_labels {
+            self.add_label(label);
+        }
+        for label in changes.removed_labels {
+            self.remove_label(&label);
+        }
+    }
+}
+
+// Properties have to encode to a JSON object, missing properties (None) are an empty one.
+pub fn encode_properties<T: Encodable>(properties: &Option<T>) -> Result<BTreeMap<String, Json>, Error> {
+    let properties = match *properties {
+        Some(ref properties) => properties,
+        None => return Ok(BTreeMap::new()),
+    };
+    let properties_raw = match json::encode(properties) {
+        Ok(s) => s,
+        Err(err) => return Err(Error::from(err)),
+    };
+    match Json::from_str(&properties_raw) {
+        Ok(Json::Object(properties)) => Ok(properties.into_iter().filter(|&(_, ref value)| *value != Json::Null).collect()),
+        Ok(_) => Err(Error::DataError(From::from(format!("Properties must be a JSON object, got: {}", properties_raw)))),
+        Err(err) => Err(Error::from(err)),
+    }
+}
+
+// Applies one property change made on the server to the local properties, as far as T can represent it, and
+// records it in the snapshot. When T can't take the change the local properties are left as they are.
+pub fn update_property<T: Encodable + Decodable>(properties: &mut Option<T>, snapshot: &mut Snapshot, key: &str, value: Option<Json>) -> Result<(), Error> {
+    let mut encoded = match encode_properties(properties) {
+        Ok(encoded) => encoded,
+        Err(err) => return Err(err),
+    };
+    match value {
+        Some(value) => encoded.insert(key.to_string(), value),
+        None => encoded.remove(key),
+    };
+    match Decodable::decode(&mut json::Decoder::new(Json::Object(encoded))) {
+        Ok(decoded) => *properties = Some(decoded),
+        Err(err) => return Err(Error::from(err)),
+    }
+
+    // The snapshot only keeps what T holds, eg. nothing for a properties type without fields.
+    let value = encode_properties(properties).ok().and_then(|mut encoded| encoded.remove(key));
+    snapshot.set_property(key.to_string(), value.unwrap_or(Json::Null));
+    Ok(())
+}
+
+pub fn encode_value<V: Encodable>(value: &V) -> Result<Json, Error> {
+    let value_raw = match json::encode(value) {
+        Ok(s) => s,
+        Err(err) => return Err(Error::from(err)),
+    };
+    match Json::from_str(&value_raw) {
+        Ok(value) => Ok(value),
+        Err(err) => Err(Error::from(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use changes::{self, Snapshot};
+    use rustc_serialize::json::Json;
+
+    #[derive(RustcEncodable)]
+    #[derive(RustcDecodable)]
+    struct TestData {
+        name: String,
+        level: i64,
+        nickname: Option<String>,
+    }
+
+    #[test]
+    pub fn test_snapshot_diff() {
+        let props = Some(TestData { name: "Steve".to_string(), level: 3, nickname: Some("Stevie".to_string()) });
+        let mut snapshot = Snapshot::take(&props, &["Person".to_string()]).unwrap();
+        assert!(snapshot.diff(&props, &["Person".to_string()]).unwrap().is_empty());
+
+        let props = Some(TestData { name: "Steve".to_string(), level: 4, nickname: None });
+        let changes = snapshot.diff(&props, &["Admin".to_string()]).unwrap();
+        assert_eq!(1, changes.set_properties.len());
+        assert_eq!(Some(&Json::U64(4)), changes.set_properties.get("level"));
+        assert_eq!(vec!["nickname".to_string()], changes.removed_properties);
+        assert_eq!(vec!["Admin".to_string()], changes.added_labels);
+        assert_eq!(vec!["Person".to_string()], changes.removed_labels);
+
+        snapshot.apply(changes);
+        assert!(snapshot.diff(&props, &["Admin".to_string()]).unwrap().is_empty());
+        assert_eq!(2, snapshot.diff::<TestData>(&None, &[]).unwrap().removed_properties.len());
+        assert!(Snapshot::take(&Some(42), &[]).is_err());
+    }
+
+    #[test]
+    pub fn test_update_property() {
+        let mut props = Some(TestData { name: "Steve".to_string(), level: 3, nickname: None });
+        let mut snapshot = Snapshot::take(&props, &[]).unwrap();
+
+        assert!(changes::update_property(&mut props, &mut snapshot, "nickname", Some(Json::String("Stevie".to_string()))).is_ok());
+        assert_eq!(Some("Stevie".to_string()), props.as_ref().unwrap().nickname);
+        assert!(snapshot.diff(&props, &[]).unwrap().is_empty());
+
+        // TestData can't be without a name.
+        assert!(changes::update_property(&mut props, &mut snapshot, "name", None).is_err());
+        assert_eq!("Steve", props.as_ref().unwrap().name);
+        assert!(changes::update_property(&mut props, &mut snapshot, "nickname", None).is_ok());
+        assert!(props.as_ref().unwrap().nickname.is_none());
+        assert!(snapshot.diff(&props, &[]).unwrap().is_empty());
+
+        props.as_mut().unwrap().level = 4;
+        assert!(snapshot.set_properties(&props).is_ok());
+        assert!(snapshot.diff(&props, &[]).unwrap().is_empty());
+    }
+}
@@ -1,8 +1,17 @@
 use rustc_serialize::{json, Encodable, Decodable};
-pub use types::Error;
+use rustc_serialize::json::Json;
+pub use types::{Error, Neo4jError};
+use types::ResponseError;
 use hyper;
 use std::io::Read;
-use std::rc::Rc;
+use std::sync::Arc;
+use client::RequestBuilder;
+use transport::Response;
+use bolt::{self, BoltConnection, BoltResult};
+use retry::RetryPolicy;
+
+#[cfg(feature = "async")] use futures::future::{self, BoxFuture, FutureExt, TryFutureExt};
+#[cfg(feature = "async")] use tokio;
 
 #[derive(RustcEncodable)]
 struct CypherStatement<T> {
@@ -21,10 +30,15 @@ pub struct CypherResult<T: Decodable> {
     pub data: T,
 }
 
+#[derive(RustcDecodable)]
+struct CypherErrorsResponse {
+    errors: Vec<Neo4jError>,
+}
+
 #[derive(RustcDecodable)]
 pub struct CypherResultsResponse<T: Decodable> {
     pub results: Vec<CypherResult<T>>,
-    pub errors: Vec<String>,
+    pub errors: Vec<Neo4jError>,
     commit: Option<String>,
 }
 
@@ -53,10 +67,94 @@ pub struct Cypher;
 
 impl Cypher {
     pub fn query<E: Encodable, D: Decodable>(cli: &::client::Client, statement: String, parameters: E) -> Result<CypherResultsResponse<D>, Error> {
-        Self::_query::<E, D>(cli, "/db/data/transaction/commit".to_string(), statement, parameters, false)
+        Self::query_with_policy(cli, cli.get_retry_policy(), statement, parameters)
+    }
+
+    // Same as `query()` retried by the given policy instead of the client's, eg. for a statement that must not be
+    // sent twice.
+    pub fn query_with_policy<E: Encodable, D: Decodable>(cli: &::client::Client, policy: &RetryPolicy, statement: String, parameters: E) -> Result<CypherResultsResponse<D>, Error> {
+        policy.run(|| {
+            match cli.get_bolt() {
+                Some(pool) => Self::bolt_query::<E, D>(pool, statement.clone(), &parameters),
+                None => match cli.endpoint(|root| root.commit_uri(None)) {
+                    Ok(path) => Self::_query::<E, D>(cli, path, statement.clone(), &parameters, false),
+                    Err(err) => Err(err),
+                },
+            }
+        })
+    }
+
+    // Runs the closure in a fresh transaction and commits it. On a failure the transaction is rolled back and,
+    // if the client's retry policy allows it, the whole closure is executed again. Network errors before the
+    // commit are always worth another attempt since nothing has been applied, a failed commit only follows the policy.
+    pub fn run_in_transaction<T, F>(cli: Arc<::client::Client>, mut f: F) -> Result<T, Error>
+        where F: FnMut(&mut CypherTransaction) -> Result<T, Error> {
+        let policy = cli.get_retry_policy().clone();
+        let transaction_policy = policy.clone().retryable(move |err| match *err {
+            Error::NetworkError(_) => true,
+            _ => policy.is_retryable(err),
+        });
+
+        let res = transaction_policy.run(|| {
+            let mut trans = CypherTransaction::new(cli.clone());
+            // On error the transaction is dropped here, which rolls it back.
+            let result = match f(&mut trans) {
+                Ok(result) => result,
+                Err(err) => return Err(err),
+            };
+
+            if trans.is_active() {
+                if let Err(err) = trans.commit() {
+                    // The commit may have been applied before the connection broke.
+                    if !cli.get_retry_policy().is_retryable(&err) {
+                        return Ok(Err(err));
+                    }
+                    return Err(err);
+                }
+            }
+            Ok(Ok(result))
+        });
+
+        match res {
+            Ok(res) => res,
+            Err(err) => Err(err),
+        }
+    }
+
+    fn _query<E: Encodable, D: Decodable>(cli: &::client::Client, path: String, statement: String, parameters: &E, is_new_transaction: bool) -> Result<CypherResultsResponse<D>, Error> {
+        let req = match Self::query_request(cli, path, statement, parameters) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+
+        let res = if is_new_transaction {
+            try_rest!(req, Created)
+        } else {
+            try_rest!(req, Ok)
+        };
+
+        from_query_response(res)
+    }
+
+    fn bolt_query<E: Encodable, D: Decodable>(pool: &bolt::BoltPool, statement: String, parameters: &E) -> Result<CypherResultsResponse<D>, Error> {
+        let parameters = match bolt_parameters(parameters) {
+            Ok(parameters) => parameters,
+            Err(err) => return Err(err),
+        };
+        let mut conn = match pool.acquire() {
+            Ok(conn) => conn,
+            Err(err) => return Err(err),
+        };
+
+        let res = conn.query(&statement, parameters);
+        pool.release(conn);
+        match res {
+            Ok(result) => from_bolt_result(result),
+            Err(err) => Err(err),
+        }
     }
 
-    fn _query<E: Encodable, D: Decodable>(cli: &::client::Client, path: String, statement: String, parameters: E, is_new_transaction: bool) -> Result<CypherResultsResponse<D>, Error> {
+    fn query_request<'c, E: Encodable>(cli: &'c ::client::Client, path: String, statement: String, parameters: &E) -> Result<RequestBuilder<'c>, Error> {
         let statement = CypherStatement {
             statement: statement,
             parameters: Some(parameters),
@@ -66,55 +164,168 @@ impl Cypher {
         };
         let payload = match json::encode(&statements) {
             Ok(s) => s,
-            _ => return Err(Error::DataError),
+            Err(err) => return Err(Error::from(err)),
         };
 
-        let mut res = if is_new_transaction {
-            try_rest!(cli.post(path).body(&payload), Created)
-        } else {
-            try_rest!(cli.post(path).body(&payload), Ok)
-        };
+        Ok(cli.post(path).body(&payload))
+    }
+}
 
-        let mut res_raw = String::new();
-        let _ = res.read_to_string(&mut res_raw);
+#[cfg(feature = "async")]
+impl Cypher {
+    pub fn query_async<E: Encodable, D: Decodable + Send + 'static>(cli: &::client::Client, statement: String, parameters: E) -> BoxFuture<'static, Result<CypherResultsResponse<D>, Error>> {
+        Self::query_with_policy_async(cli, cli.get_retry_policy(), statement, parameters)
+    }
 
-        let result: CypherResultsResponse<D> = match json::decode(&res_raw) {
-            Ok(obj) => obj,
-            _ => return Err(Error::DataError),
+    pub fn query_with_policy_async<E: Encodable, D: Decodable + Send + 'static>(cli: &::client::Client, policy: &RetryPolicy, statement: String, parameters: E) -> BoxFuture<'static, Result<CypherResultsResponse<D>, Error>> {
+        let path = match cli.endpoint(|root| root.commit_uri(None)) {
+            Ok(path) => path,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        let req = match Self::query_request(cli, path, statement, &parameters) {
+            Ok(req) => req.into_async(),
+            Err(err) => return future::ready(Err(err)).boxed(),
         };
 
-        Ok(result)
+        policy.run_async(move || {
+            try_rest_async!(req, Ok)
+                .map(|res| res.and_then(from_query_response))
+                .boxed()
+        })
+    }
+}
+
+fn from_query_response<D: Decodable>(mut res: Response) -> Result<CypherResultsResponse<D>, Error> {
+    let mut res_raw = String::new();
+    let _ = res.read_to_string(&mut res_raw);
+
+    let result: CypherResultsResponse<D> = match json::decode(&res_raw) {
+        Ok(obj) => obj,
+        Err(err) => return Err(Error::from(err)),
+    };
+
+    // Statement failures come back with a success status, the errors are only in the payload.
+    if !result.errors.is_empty() {
+        return Err(Error::from(ResponseError {
+            status: res.status,
+            body: res_raw,
+            errors: result.errors,
+        }));
+    }
+
+    Ok(result)
+}
+
+fn bolt_parameters<E: Encodable>(parameters: &E) -> Result<bolt::Value, Error> {
+    let parameters_raw = match json::encode(parameters) {
+        Ok(s) => s,
+        Err(err) => return Err(Error::from(err)),
+    };
+    match Json::from_str(&parameters_raw) {
+        Ok(parameters_json) => Ok(bolt::Value::from_json(&parameters_json)),
+        Err(err) => Err(Error::from(err)),
+    }
+}
+
+// Bolt records are decoded the same way as the rows of the REST endpoints, a single statement result.
+fn from_bolt_result<D: Decodable>(result: BoltResult) -> Result<CypherResultsResponse<D>, Error> {
+    let rows: Vec<Json> = result.rows.iter().map(|row| {
+        let mut data = json::Object::new();
+        data.insert("row".to_string(), Json::Array(row.iter().map(bolt::Value::to_json).collect()));
+        Json::Object(data)
+    }).collect();
+
+    let mut statement_result = json::Object::new();
+    statement_result.insert("columns".to_string(), Json::Array(result.columns.into_iter().map(Json::String).collect()));
+    statement_result.insert("data".to_string(), Json::Array(rows));
+
+    let mut response = json::Object::new();
+    response.insert("results".to_string(), Json::Array(vec![Json::Object(statement_result)]));
+    response.insert("errors".to_string(), Json::Array(vec![]));
+
+    let mut decoder = json::Decoder::new(Json::Object(response));
+    match Decodable::decode(&mut decoder) {
+        Ok(result) => Ok(result),
+        Err(err) => Err(Error::from(err)),
     }
 }
 
+fn from_commit_response(mut res: Response) -> Result<(), Error> {
+    let mut res_raw = String::new();
+    let _ = res.read_to_string(&mut res_raw);
+    if let Ok(CypherErrorsResponse { errors }) = json::decode(&res_raw) {
+        if !errors.is_empty() {
+            return Err(Error::from(ResponseError {
+                status: res.status,
+                body: res_raw,
+                errors: errors,
+            }));
+        }
+    }
+    Ok(())
+}
+
+// An open transaction is rolled back when it goes out of scope without being committed, see `into_detached()`
+// for keeping it open on the server.
 pub struct CypherTransaction {
-    cli: Rc<::client::Client>,
+    cli: Arc<::client::Client>,
     id: Option<u64>,
+    // Over Bolt the transaction lives on its connection and has no id.
+    bolt: Option<BoltConnection>,
 }
 
 impl CypherTransaction {
-    pub fn new(cli: Rc<::client::Client>) -> CypherTransaction {
+    pub fn new(cli: Arc<::client::Client>) -> CypherTransaction {
         CypherTransaction {
             id: None,
             cli: cli,
+            bolt: None,
+        }
+    }
+
+    // Continues a transaction that was detached earlier.
+    pub fn attach(cli: Arc<::client::Client>, id: u64) -> CypherTransaction {
+        CypherTransaction {
+            id: Some(id),
+            cli: cli,
+            bolt: None,
         }
     }
 
+    pub fn get_id(&self) -> Option<u64> {
+        self.id
+    }
+
     fn has_id(&self) -> bool {
         self.id.is_some()
     }
 
-    fn is_active(&self) -> bool {
-        self.has_id()
+    pub fn is_active(&self) -> bool {
+        self.has_id() || self.bolt.is_some()
+    }
+
+    // Releases the transaction without rolling it back, it stays open on the server until it is committed
+    // (through `attach()`) or it times out. Returns the id of the transaction if it has been started.
+    // Bolt transactions can't outlive their connection, they are rolled back.
+    pub fn into_detached(mut self) -> Option<u64> {
+        self.id.take()
+    }
+
+    fn query_path(&self) -> Result<String, Error> {
+        let id = self.id;
+        self.cli.endpoint(|root| root.transaction_uri(id))
     }
 
     pub fn query<E: Encodable, D: Decodable>(&mut self, statement: String, parameters: E) -> Result<CypherResultsResponse<D>, Error> {
-        let path = if self.is_active() {
-            format!("/db/data/transaction/{}", self.id.unwrap())
-        } else {
-            "/db/data/transaction".to_string()
+        if self.cli.get_bolt().is_some() {
+            return self.bolt_query::<E, D>(statement, &parameters);
+        }
+
+        let path = match self.query_path() {
+            Ok(path) => path,
+            Err(err) => return Err(err),
         };
-        let res = Cypher::_query::<E, D>(self.cli.as_ref(), path, statement, parameters, !self.is_active());
+        let res = Cypher::_query::<E, D>(self.cli.as_ref(), path, statement, &parameters, !self.is_active());
         if res.is_err() {
             return res;
         }
@@ -126,35 +337,216 @@ impl CypherTransaction {
         res
     }
 
-    pub fn commit(&mut self) -> Result<(), Error> {
+    fn bolt_query<E: Encodable, D: Decodable>(&mut self, statement: String, parameters: &E) -> Result<CypherResultsResponse<D>, Error> {
+        let parameters = match bolt_parameters(parameters) {
+            Ok(parameters) => parameters,
+            Err(err) => return Err(err),
+        };
+
+        if self.bolt.is_none() {
+            let pool = self.cli.get_bolt().unwrap();
+            let mut conn = match pool.acquire() {
+                Ok(conn) => conn,
+                Err(err) => return Err(err),
+            };
+            if let Err(err) = conn.begin() {
+                pool.release(conn);
+                return Err(err);
+            }
+            self.bolt = Some(conn);
+        }
+
+        let res = self.bolt.as_mut().unwrap().query(&statement, parameters);
+        match res {
+            Ok(result) => from_bolt_result(result),
+            Err(err) => {
+                // The failure ended the transaction on the server, the connection has been reset.
+                self.release_bolt();
+                Err(err)
+            },
+        }
+    }
+
+    fn release_bolt(&mut self) {
+        if let Some(conn) = self.bolt.take() {
+            self.cli.get_bolt().unwrap().release(conn);
+        }
+    }
+
+    pub fn commit(mut self) -> Result<(), Error> {
+        if self.bolt.is_some() {
+            let res = self.bolt.as_mut().unwrap().commit();
+            self.release_bolt();
+            return res;
+        }
+
         if !self.is_active() {
             return Err(Error::IntegrityError);
         }
-        let path = format!("/db/data/transaction/{}/commit", self.id.unwrap());
-        try_rest!(self.cli.as_ref().post(path));
+        let id = self.id;
+        let path = match self.cli.endpoint(|root| root.commit_uri(id)) {
+            Ok(path) => path,
+            Err(err) => return Err(err),
+        };
+        let res = try_rest!(self.cli.as_ref().post(path));
         self.id = None;
-        Ok(())
+
+        from_commit_response(res)
     }
 
-    pub fn rollback(&mut self) -> Result<(), Error> {
+    pub fn rollback(mut self) -> Result<(), Error> {
+        self._rollback()
+    }
+
+    fn _rollback(&mut self) -> Result<(), Error> {
+        if self.bolt.is_some() {
+            let res = self.bolt.as_mut().unwrap().rollback();
+            self.release_bolt();
+            return res;
+        }
+
         if !self.is_active() {
             return Err(Error::IntegrityError);
         }
-        let path = format!("/db/data/transaction/{}", self.id.unwrap());
-        try_rest!(self.cli.as_ref().delete(path));
+        let path = match self.query_path() {
+            Ok(path) => path,
+            Err(err) => return Err(err),
+        };
         self.id = None;
+        try_rest!(self.cli.as_ref().delete(path));
         Ok(())
     }
 }
 
+// Non-blocking variants. Unlike the blocking commit, a failed `commit_async()` does not roll the transaction
+// back, the server discards it after its timeout.
+#[cfg(feature = "async")]
+impl CypherTransaction {
+    pub fn query_async<'a, E: Encodable, D: Decodable + Send + 'static>(&'a mut self, statement: String, parameters: E) -> BoxFuture<'a, Result<CypherResultsResponse<D>, Error>> {
+        let path = match self.query_path() {
+            Ok(path) => path,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        let req = match Cypher::query_request(self.cli.as_ref(), path, statement, &parameters) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+
+        let res = if self.is_active() {
+            try_rest_async!(req, Ok).boxed()
+        } else {
+            try_rest_async!(req, Created).boxed()
+        };
+
+        res.map(move |res| {
+            let res = res.and_then(from_query_response::<D>);
+            if let Ok(ref result) = res {
+                if !self.has_id() {
+                    self.id = result.get_id();
+                }
+            }
+            res
+        }).boxed()
+    }
+
+    pub fn commit_async(mut self) -> BoxFuture<'static, Result<(), Error>> {
+        let id = match self.id.take() {
+            Some(id) => id,
+            None => return future::ready(Err(Error::IntegrityError)).boxed(),
+        };
+        let path = match self.cli.endpoint(|root| root.commit_uri(Some(id))) {
+            Ok(path) => path,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(self.cli.as_ref().post(path))
+            .map(|res| res.and_then(from_commit_response))
+            .boxed()
+    }
+
+    pub fn rollback_async(mut self) -> BoxFuture<'static, Result<(), Error>> {
+        let id = match self.id.take() {
+            Some(id) => id,
+            None => return future::ready(Err(Error::IntegrityError)).boxed(),
+        };
+        let path = match self.cli.endpoint(|root| root.transaction_uri(Some(id))) {
+            Ok(path) => path,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(self.cli.as_ref().delete(path))
+            .map_ok(|_| ())
+            .boxed()
+    }
+
+    // Inside a tokio runtime the rollback on drop is spawned on it instead of blocking the worker thread.
+    // Returns false when there is no runtime or the transaction runs over Bolt.
+    fn spawn_rollback(&mut self) -> bool {
+        let runtime = match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => runtime,
+            Err(_) => return false,
+        };
+        let id = match self.id {
+            Some(id) if self.bolt.is_none() => id,
+            _ => return false,
+        };
+        // The endpoints have been discovered by the queries of the transaction, this does not block.
+        let path = match self.query_path() {
+            Ok(path) => path,
+            Err(err) => {
+                warn!("Transaction {} could not be rolled back on drop: {}", id, err);
+                return true;
+            },
+        };
+        self.id = None;
+
+        runtime.spawn(try_rest_async!(self.cli.as_ref().delete(path)).map(move |res| match res {
+            Ok(_) => info!("Transaction {} rolled back on drop", id),
+            Err(err) => warn!("Transaction {} could not be rolled back on drop: {}", id, err),
+        }));
+        true
+    }
+}
+
+// Outside of a tokio runtime, or over Bolt, the rollback on drop is a blocking call.
+impl Drop for CypherTransaction {
+    fn drop(&mut self) {
+        if !self.is_active() {
+            return;
+        }
+        #[cfg(feature = "async")]
+        {
+            if self.spawn_rollback() {
+                return;
+            }
+        }
+
+        let id = match self.id {
+            Some(id) => id.to_string(),
+            None => "on Bolt connection".to_string(),
+        };
+        match self._rollback() {
+            Ok(_) => info!("Transaction {} rolled back on drop", id),
+            Err(err) => warn!("Transaction {} could not be rolled back on drop: {}", id, err),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::env;
+    use std::io;
     use client;
     use cypher;
     use node;
     use std::collections::HashMap;
-    use std::rc::Rc;
+    use std::sync::Arc;
+    use hyper::method::Method;
+    use hyper::status::StatusCode;
+    use std::time::Duration;
+    use transport::MockTransport;
+    use retry::RetryPolicy;
+    use types::{Classification, Error};
+    use bolt::{self, Value};
+    use bolt::stub::{self, StubServer};
 
     #[derive(RustcEncodable, RustcDecodable)]
     struct TestNodeProps {
@@ -177,6 +569,25 @@ mod tests {
             .get()
     }
 
+    fn get_mock_client(transport: &MockTransport) -> ::client::Client {
+        let builder = client::ClientBuilder::new()
+            .discovery(false)
+            .transport(transport.clone());
+        #[cfg(feature = "async")]
+        let builder = builder.async_transport(transport.clone());
+        builder.get()
+    }
+
+    fn get_mock_client_with_retry(transport: &MockTransport) -> ::client::Client {
+        let builder = client::ClientBuilder::new()
+            .discovery(false)
+            .transport(transport.clone())
+            .retry_policy(RetryPolicy::new().max_attempts(3).initial_backoff(Duration::from_millis(0)));
+        #[cfg(feature = "async")]
+        let builder = builder.async_transport(transport.clone());
+        builder.get()
+    }
+
     #[test]
     pub fn test_simple_query_with_immediate_commit() {
         let cli = get_client();
@@ -197,7 +608,7 @@ mod tests {
 
     #[test]
     pub fn test_simple_query_with_transaction() {
-        let cli = Rc::new(get_client());
+        let cli = Arc::new(get_client());
 
         let mut node = node::Node::new();
         node.set_properties(TestNodeProps { name: "Steve".to_string() });
@@ -206,9 +617,10 @@ mod tests {
         let mut params = HashMap::new();
         params.insert("id".to_string(), node.get_id().unwrap());
 
+        assert!(cypher::CypherTransaction::new(cli.clone()).commit().is_err());
+        assert!(cypher::CypherTransaction::new(cli.clone()).rollback().is_err());
+
         let mut trans = cypher::CypherTransaction::new(cli.clone());
-        assert!(trans.commit().is_err());
-        assert!(trans.rollback().is_err());
 
         let res = trans.query::<HashMap<String, u64>, Vec<TestQueryResult>>("START n=node({id}) RETURN n.name".to_string(), params);
         assert!(res.is_ok());
@@ -217,4 +629,356 @@ mod tests {
 
         assert!(node.delete(cli.as_ref()).is_ok());
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn test_transaction_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Created, r#"{"commit":"http://localhost:7474/db/data/transaction/9/commit","results":[{"columns":["n.name"],"data":[{"row":["Steve"]}]}],"errors":[]}"#);
+        transport.push_response(StatusCode::Ok, r#"{"commit":"http://localhost:7474/db/data/transaction/9/commit","results":[{"columns":["n.name"],"data":[{"row":["Steve"]}]}],"errors":[]}"#);
+        transport.push_response(StatusCode::Ok, r#"{"results":[],"errors":[]}"#);
+        let cli = Arc::new(get_mock_client(&transport));
+
+        let mut params = HashMap::new();
+        params.insert("id".to_string(), 1);
+
+        let mut trans = cypher::CypherTransaction::new(cli.clone());
+        let res = trans.query::<HashMap<String, u64>, Vec<TestQueryResult>>("START n=node({id}) RETURN n.name".to_string(), params.clone());
+        assert_eq!(res.unwrap().results[0].data[0].row[0], "Steve");
+        assert!(trans.query::<HashMap<String, u64>, Vec<TestQueryResult>>("START n=node({id}) RETURN n.name".to_string(), params).is_ok());
+        assert!(trans.commit().is_ok());
+
+        let requests = transport.requests();
+        assert_eq!(3, requests.len());
+        assert_eq!("http://localhost:7474/db/data/transaction", requests[0].uri);
+        assert_eq!("http://localhost:7474/db/data/transaction/9", requests[1].uri);
+        assert_eq!(Method::Post, requests[2].method);
+        assert_eq!("http://localhost:7474/db/data/transaction/9/commit", requests[2].uri);
+    }
+
+    #[test]
+    pub fn test_query_errors_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Ok, r#"{"results":[],"errors":[{"code":"Neo.TransientError.Transaction.DeadlockDetected","message":"ForsetiClient can't acquire ExclusiveLock"}]}"#);
+        let cli = get_mock_client(&transport);
+
+        let res = cypher::Cypher::query::<(), Vec<TestQueryResult>>(&cli, "MATCH (n) SET n.name = 'x'".to_string(), ());
+        let err = match res {
+            Err(err) => err,
+            Ok(_) => panic!("Expected query error"),
+        };
+        assert!(err.is_transient());
+        assert!(err.has_neo4j_status(Classification::TransientError, "Transaction", "DeadlockDetected"));
+        assert_eq!("ForsetiClient can't acquire ExclusiveLock", err.neo4j_errors()[0].message);
+    }
+
+    #[test]
+    pub fn test_credentials_expired_errors_with_mock() {
+        let transport = MockTransport::new();
+        let expired = r#"{"results":[],"errors":[{"code":"Neo.ClientError.Security.CredentialsExpired","message":"Password change required"}]}"#;
+        transport.push_response(StatusCode::Ok, expired);
+        transport.push_response(StatusCode::Created, r#"{"commit":"http://localhost:7474/db/data/transaction/9/commit","results":[],"errors":[]}"#);
+        transport.push_response(StatusCode::Ok, expired);
+        let cli = Arc::new(get_mock_client(&transport));
+
+        match cypher::Cypher::query::<(), Vec<TestQueryResult>>(&cli, "MATCH (n) RETURN n.name".to_string(), ()) {
+            Err(Error::PasswordChangeRequired(_)) => {},
+            _ => panic!("Expected password change required"),
+        }
+
+        let mut trans = cypher::CypherTransaction::new(cli.clone());
+        assert!(trans.query::<(), Vec<TestQueryResult>>("MATCH (n) RETURN n.name".to_string(), ()).is_ok());
+        match trans.commit() {
+            Err(Error::PasswordChangeRequired(_)) => {},
+            _ => panic!("Expected password change required"),
+        }
+    }
+
+    #[test]
+    pub fn test_query_retries_transient_errors_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Ok, r#"{"results":[],"errors":[{"code":"Neo.TransientError.Transaction.DeadlockDetected","message":"Deadlock"}]}"#);
+        transport.push_response(StatusCode::Ok, r#"{"results":[{"columns":["n.name"],"data":[{"row":["Steve"]}]}],"errors":[]}"#);
+        transport.push_response(StatusCode::Ok, r#"{"results":[],"errors":[{"code":"Neo.ClientError.Statement.SyntaxError","message":"Invalid input"}]}"#);
+        let cli = get_mock_client_with_retry(&transport);
+
+        let res = cypher::Cypher::query::<(), Vec<TestQueryResult>>(&cli, "MATCH (n) RETURN n.name".to_string(), ());
+        assert_eq!(res.unwrap().results[0].data[0].row[0], "Steve");
+        assert_eq!(2, transport.requests().len());
+
+        let res = cypher::Cypher::query::<(), Vec<TestQueryResult>>(&cli, "MATCH (n RETURN n.name".to_string(), ());
+        assert!(res.is_err());
+        assert_eq!(3, transport.requests().len());
+    }
+
+    #[test]
+    pub fn test_query_retries_only_connect_failures_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_error(Error::from(io::Error::new(io::ErrorKind::ConnectionReset, "reset")));
+        transport.push_error(Error::from_connect_failure(io::Error::new(io::ErrorKind::ConnectionRefused, "refused")));
+        transport.push_response(StatusCode::Ok, r#"{"results":[],"errors":[]}"#);
+        let cli = get_mock_client_with_retry(&transport);
+
+        // The statement may have been applied before the connection broke, it is not sent again.
+        assert!(cypher::Cypher::query::<(), cypher::CypherUnidentifiedData>(&cli, "CREATE (n)".to_string(), ()).is_err());
+        assert_eq!(1, transport.requests().len());
+
+        assert!(cypher::Cypher::query::<(), cypher::CypherUnidentifiedData>(&cli, "CREATE (n)".to_string(), ()).is_ok());
+        assert_eq!(3, transport.requests().len());
+    }
+
+    #[test]
+    pub fn test_run_in_transaction_retries_network_errors_before_commit_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_error(Error::from(io::Error::new(io::ErrorKind::ConnectionReset, "reset")));
+        transport.push_response(StatusCode::Created, r#"{"commit":"http://localhost:7474/db/data/transaction/10/commit","results":[],"errors":[]}"#);
+        transport.push_response(StatusCode::Ok, r#"{"results":[],"errors":[]}"#);
+        // A broken commit is not repeated, the transaction is rolled back on drop.
+        transport.push_response(StatusCode::Created, r#"{"commit":"http://localhost:7474/db/data/transaction/11/commit","results":[],"errors":[]}"#);
+        transport.push_error(Error::from(io::Error::new(io::ErrorKind::ConnectionReset, "reset")));
+        transport.push_response(StatusCode::Ok, r#"{"results":[],"errors":[]}"#);
+        let cli = Arc::new(get_mock_client_with_retry(&transport));
+
+        let res = cypher::Cypher::run_in_transaction(cli.clone(), |trans| {
+            trans.query::<(), Vec<TestQueryResult>>("CREATE (n) RETURN n".to_string(), ()).map(|_| ())
+        });
+        assert!(res.is_ok());
+        assert_eq!(3, transport.requests().len());
+
+        let res = cypher::Cypher::run_in_transaction(cli.clone(), |trans| {
+            trans.query::<(), Vec<TestQueryResult>>("CREATE (n) RETURN n".to_string(), ()).map(|_| ())
+        });
+        assert!(res.is_err());
+        let requests = transport.requests();
+        assert_eq!(6, requests.len());
+        assert_eq!(Method::Delete, requests[5].method);
+        assert_eq!("http://localhost:7474/db/data/transaction/11", requests[5].uri);
+    }
+
+    #[test]
+    pub fn test_run_in_transaction_with_mock() {
+        let transport = MockTransport::new();
+        // First attempt: the second statement deadlocks, the transaction gets rolled back.
+        transport.push_response(StatusCode::Created, r#"{"commit":"http://localhost:7474/db/data/transaction/9/commit","results":[],"errors":[]}"#);
+        transport.push_response(StatusCode::Ok, r#"{"results":[],"errors":[{"code":"Neo.TransientError.Transaction.DeadlockDetected","message":"Deadlock"}]}"#);
+        transport.push_response(StatusCode::Ok, r#"{"results":[],"errors":[]}"#);
+        // Second attempt succeeds and commits.
+        transport.push_response(StatusCode::Created, r#"{"commit":"http://localhost:7474/db/data/transaction/10/commit","results":[],"errors":[]}"#);
+        transport.push_response(StatusCode::Ok, r#"{"commit":"http://localhost:7474/db/data/transaction/10/commit","results":[],"errors":[]}"#);
+        transport.push_response(StatusCode::Ok, r#"{"results":[],"errors":[]}"#);
+        let cli = Arc::new(get_mock_client_with_retry(&transport));
+
+        let mut attempts = 0;
+        let res = cypher::Cypher::run_in_transaction(cli.clone(), |trans| {
+            attempts += 1;
+            if let Err(err) = trans.query::<(), Vec<TestQueryResult>>("CREATE (n) RETURN n".to_string(), ()) {
+                return Err(err);
+            }
+            if let Err(err) = trans.query::<(), Vec<TestQueryResult>>("CREATE (m) RETURN m".to_string(), ()) {
+                return Err(err);
+            }
+            Ok(attempts)
+        });
+        assert_eq!(2, res.unwrap());
+
+        let requests = transport.requests();
+        assert_eq!(6, requests.len());
+        assert_eq!(Method::Delete, requests[2].method);
+        assert_eq!("http://localhost:7474/db/data/transaction/9", requests[2].uri);
+        assert_eq!("http://localhost:7474/db/data/transaction/10/commit", requests[5].uri);
+    }
+
+    #[test]
+    pub fn test_transaction_rolled_back_on_drop_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Created, r#"{"commit":"http://localhost:7474/db/data/transaction/9/commit","results":[],"errors":[]}"#);
+        transport.push_response(StatusCode::Ok, r#"{"results":[],"errors":[]}"#);
+        let cli = Arc::new(get_mock_client(&transport));
+
+        {
+            let mut trans = cypher::CypherTransaction::new(cli.clone());
+            assert!(trans.query::<(), Vec<TestQueryResult>>("CREATE (n) RETURN n".to_string(), ()).is_ok());
+        }
+
+        {
+            // Never started, nothing to roll back.
+            let _trans = cypher::CypherTransaction::new(cli.clone());
+        }
+
+        let requests = transport.requests();
+        assert_eq!(2, requests.len());
+        assert_eq!(Method::Delete, requests[1].method);
+        assert_eq!("http://localhost:7474/db/data/transaction/9", requests[1].uri);
+    }
+
+    #[test]
+    pub fn test_transaction_detach_and_attach_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Created, r#"{"commit":"http://localhost:7474/db/data/transaction/9/commit","results":[],"errors":[]}"#);
+        transport.push_response(StatusCode::Ok, r#"{"results":[],"errors":[]}"#);
+        let cli = Arc::new(get_mock_client(&transport));
+
+        let mut trans = cypher::CypherTransaction::new(cli.clone());
+        assert!(trans.query::<(), Vec<TestQueryResult>>("CREATE (n) RETURN n".to_string(), ()).is_ok());
+        let id = trans.into_detached();
+        assert_eq!(Some(9), id);
+        assert_eq!(1, transport.requests().len());
+
+        let trans = cypher::CypherTransaction::attach(cli.clone(), id.unwrap());
+        assert!(trans.commit().is_ok());
+
+        let requests = transport.requests();
+        assert_eq!(2, requests.len());
+        assert_eq!(Method::Post, requests[1].method);
+        assert_eq!("http://localhost:7474/db/data/transaction/9/commit", requests[1].uri);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    pub fn test_transaction_async_with_mock() {
+        use futures::executor::block_on;
+
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Created, r#"{"commit":"http://localhost:7474/db/data/transaction/9/commit","results":[{"columns":["n.name"],"data":[{"row":["Steve"]}]}],"errors":[]}"#);
+        transport.push_response(StatusCode::Ok, r#"{"results":[],"errors":[]}"#);
+        let cli = Arc::new(get_mock_client(&transport));
+
+        let mut trans = cypher::CypherTransaction::new(cli.clone());
+        let res = block_on(trans.query_async::<(), Vec<TestQueryResult>>("MATCH (n) RETURN n.name".to_string(), ()));
+        assert_eq!(res.unwrap().results[0].data[0].row[0], "Steve");
+        assert_eq!(Some(9), trans.get_id());
+        assert!(block_on(trans.commit_async()).is_ok());
+
+        let requests = transport.requests();
+        assert_eq!(2, requests.len());
+        assert_eq!("http://localhost:7474/db/data/transaction", requests[0].uri);
+        assert_eq!("http://localhost:7474/db/data/transaction/9/commit", requests[1].uri);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    pub fn test_transaction_rolled_back_on_drop_in_runtime_with_mock() {
+        use futures::executor::block_on;
+
+        let transport = MockTransport::new();
+        let async_transport = MockTransport::new();
+        async_transport.push_response(StatusCode::Created, r#"{"commit":"http://localhost:7474/db/data/transaction/9/commit","results":[],"errors":[]}"#);
+        async_transport.push_response(StatusCode::Ok, r#"{"results":[],"errors":[]}"#);
+        let cli = Arc::new(client::ClientBuilder::new()
+            .discovery(false)
+            .transport(transport.clone())
+            .async_transport(async_transport.clone())
+            .get());
+
+        let mut trans = cypher::CypherTransaction::new(cli.clone());
+        assert!(block_on(trans.query_async::<(), Vec<TestQueryResult>>("CREATE (n) RETURN n".to_string(), ())).is_ok());
+
+        let runtime = ::tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        let _guard = runtime.enter();
+        drop(trans);
+        runtime.block_on(::tokio::time::sleep(Duration::from_millis(10)));
+
+        assert!(transport.requests().is_empty());
+        let requests = async_transport.requests();
+        assert_eq!(2, requests.len());
+        assert_eq!(Method::Delete, requests[1].method);
+        assert_eq!("http://localhost:7474/db/data/transaction/9", requests[1].uri);
+        assert_eq!(0, async_transport.pending_responses());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    pub fn test_query_async_retries_transient_errors_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Ok, r#"{"results":[],"errors":[{"code":"Neo.TransientError.Transaction.DeadlockDetected","message":"Deadlock"}]}"#);
+        transport.push_response(StatusCode::Ok, r#"{"results":[{"columns":["n.name"],"data":[{"row":["Steve"]}]}],"errors":[]}"#);
+        let cli = get_mock_client_with_retry(&transport);
+
+        let runtime = ::tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        let res = runtime.block_on(cypher::Cypher::query_async::<(), Vec<TestQueryResult>>(&cli, "MATCH (n) RETURN n.name".to_string(), ()));
+        assert_eq!(res.unwrap().results[0].data[0].row[0], "Steve");
+        assert_eq!(2, transport.requests().len());
+    }
+
+    fn get_bolt_client(server: &StubServer) -> ::client::Client {
+        client::ClientBuilder::new()
+            .credential("neo4j".to_string(), "secret".to_string())
+            .bolt(server.get_address())
+            .get()
+    }
+
+    #[test]
+    pub fn test_bolt_query_with_mock_server() {
+        let server = StubServer::start(vec![
+            stub::hello(),
+            vec![stub::success(vec![("fields", Value::List(vec![Value::from("n.name")]))])],
+            vec![stub::record(vec![Value::from("Steve")]), stub::success(vec![])],
+        ]);
+        let cli = get_bolt_client(&server);
+
+        let mut params = HashMap::new();
+        params.insert("id".to_string(), 1);
+        let res = cypher::Cypher::query::<HashMap<String, u64>, Vec<TestQueryResult>>(&cli, "MATCH (n) WHERE id(n) = $id RETURN n.name".to_string(), params);
+        let res = res.unwrap();
+        assert_eq!(vec!["n.name".to_string()], res.results[0].columns);
+        assert_eq!(res.results[0].data[0].row[0], "Steve");
+
+        let received = server.received();
+        assert_eq!(bolt::message(bolt::RUN, vec![
+            Value::from("MATCH (n) WHERE id(n) = $id RETURN n.name"),
+            bolt::map(vec![("id", Value::Integer(1))]),
+            bolt::map(vec![]),
+        ]), received[1]);
+    }
+
+    #[test]
+    pub fn test_bolt_transaction_with_mock_server() {
+        let server = StubServer::start(vec![
+            stub::hello(),
+            vec![stub::success(vec![])],
+            vec![stub::success(vec![("fields", Value::List(vec![Value::from("n.name")]))])],
+            vec![stub::record(vec![Value::from("Steve")]), stub::success(vec![])],
+            vec![stub::success(vec![("bookmark", Value::from("FB:1"))])],
+            vec![stub::success(vec![])],
+            vec![stub::success(vec![])],
+            vec![stub::success(vec![("fields", Value::List(vec![]))])],
+            vec![stub::success(vec![])],
+            vec![stub::success(vec![])],
+        ]);
+        let cli = Arc::new(get_bolt_client(&server));
+
+        let mut trans = cypher::CypherTransaction::new(cli.clone());
+        let res = trans.query::<(), Vec<TestQueryResult>>("MATCH (n) RETURN n.name".to_string(), ());
+        assert_eq!(res.unwrap().results[0].data[0].row[0], "Steve");
+        assert!(trans.is_active());
+        assert_eq!(None, trans.get_id());
+        assert!(trans.commit().is_ok());
+
+        {
+            let mut trans = cypher::CypherTransaction::new(cli.clone());
+            assert!(trans.query::<(), cypher::CypherUnidentifiedData>("CREATE (n)".to_string(), ()).is_ok());
+        }
+
+        assert_eq!(vec![bolt::HELLO, bolt::BEGIN, bolt::RUN, bolt::PULL, bolt::COMMIT, bolt::RESET, bolt::BEGIN, bolt::RUN, bolt::PULL, bolt::ROLLBACK], server.received_signatures());
+    }
+
+    #[test]
+    pub fn test_bolt_query_errors_with_mock_server() {
+        let server = StubServer::start(vec![
+            stub::hello(),
+            vec![stub::success(vec![])],
+            vec![stub::failure("Neo.ClientError.Statement.SyntaxError", "Invalid input")],
+            vec![stub::ignored()],
+            vec![stub::success(vec![])],
+        ]);
+        let cli = Arc::new(get_bolt_client(&server));
+
+        let mut trans = cypher::CypherTransaction::new(cli.clone());
+        let err = match trans.query::<(), Vec<TestQueryResult>>("MATCH (n RETURN n.name".to_string(), ()) {
+            Err(err) => err,
+            Ok(_) => panic!("Expected query error"),
+        };
+        assert!(err.has_neo4j_status(Classification::ClientError, "Statement", "SyntaxError"));
+        assert!(!trans.is_active());
+        assert_eq!(vec![bolt::HELLO, bolt::BEGIN, bolt::RUN, bolt::PULL, bolt::RESET], server.received_signatures());
+    }
+}
@@ -1,8 +1,11 @@
 use rustc_serialize::{json};
 use std::collections::HashMap;
 use hyper;
+use client::RequestBuilder;
 pub use types::Error;
 
+#[cfg(feature = "async")] use futures::future::{self, BoxFuture, FutureExt, TryFutureExt};
+
 #[cfg(test)] extern crate rand;
 
 pub struct Index {
@@ -19,21 +22,64 @@ impl Index {
     }
 
     pub fn create(&self, cli: &::client::Client) -> Result<(), Error> {
-        let path = format!("/db/data/schema/index/{}", self.label);
+        let req = match self.create_request(cli) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        try_rest!(req, Ok);
+        Ok(())
+    }
+
+    fn create_request<'c>(&self, cli: &'c ::client::Client) -> Result<RequestBuilder<'c>, Error> {
+        let path = match cli.endpoint(|root| root.index_uri(&self.label)) {
+            Ok(path) => path,
+            Err(err) => return Err(err),
+        };
 
         let mut payload_data: HashMap<String, Vec<String>> = HashMap::new();
         payload_data.insert("property_keys".to_string(), vec![self.property_key.clone()]);
         let payload = json::encode(&payload_data).unwrap();
 
-        try_rest!(cli.post(path).body(&*payload), Ok);
-        Ok(())
+        Ok(cli.post(path).body(&*payload))
     }
 
     pub fn delete(&self, cli: &::client::Client) -> Result<(), Error> {
-        let path = format!("/db/data/schema/index/{}/{}", self.label, self.property_key);
-        try_rest!(cli.delete(path), NoContent);
+        let req = match self.delete_request(cli) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        try_rest!(req, NoContent);
         Ok(())
     }
+
+    fn delete_request<'c>(&self, cli: &'c ::client::Client) -> Result<RequestBuilder<'c>, Error> {
+        match cli.endpoint(|root| format!("{}/{}", root.index_uri(&self.label), self.property_key)) {
+            Ok(path) => Ok(cli.delete(path)),
+            Err(err) => Err(err),
+        }
+    }
+
+    #[cfg(feature = "async")]
+    pub fn create_async(&self, cli: &::client::Client) -> BoxFuture<'static, Result<(), Error>> {
+        let req = match self.create_request(cli) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(req, Ok)
+            .map_ok(|_| ())
+            .boxed()
+    }
+
+    #[cfg(feature = "async")]
+    pub fn delete_async(&self, cli: &::client::Client) -> BoxFuture<'static, Result<(), Error>> {
+        let req = match self.delete_request(cli) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(req, NoContent)
+            .map_ok(|_| ())
+            .boxed()
+    }
 }
 
 #[cfg(test)]
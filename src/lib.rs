@@ -6,13 +6,33 @@ extern crate rustc_serialize;
 #[macro_use]
 extern crate log;
 
-#[cfg(test)] extern crate rand;
+extern crate rand;
+extern crate native_tls;
+extern crate url;
+
+#[cfg(feature = "async")] extern crate bytes;
+#[cfg(feature = "async")] extern crate futures;
+#[cfg(feature = "async")] extern crate http;
+#[cfg(feature = "async")] extern crate http_body_util;
+#[cfg(feature = "async")] extern crate hyper_tls;
+#[cfg(feature = "async")] extern crate hyper_util;
+#[cfg(feature = "async")] extern crate tokio;
+#[cfg(feature = "async")] extern crate tokio_native_tls;
+#[cfg(feature = "async")] extern crate tower_service;
 
 mod macros;
 pub mod types;
+pub mod changes;
+pub mod transport;
+pub mod bolt;
+pub mod retry;
+pub mod auth;
+pub mod service;
 pub mod client;
 pub mod node;
 pub mod relationship;
 pub mod index;
 pub mod path;
+pub mod user;
 pub mod cypher;
+pub mod batch;
@@ -2,7 +2,7 @@
 
 macro_rules! request_fn {
     ($name:ident, $req_type:ident) => {
-        pub fn $name(&self, path: String) -> hyper::client::RequestBuilder {
+        pub fn $name(&self, path: String) -> RequestBuilder<'_> {
             self.request(hyper::method::Method::$req_type, path)
         }
     }
@@ -11,23 +11,40 @@ macro_rules! request_fn {
 macro_rules! expect_code {
     ($response:expr, $code:ident) => (
         if hyper::status::StatusCode::$code != $response.status {
-            return Err(Error::ResponseError);
+            return Err(::types::Error::from_response($response));
         }
     )
 }
 
 macro_rules! try_rest {
     ($query:expr) => (
-        try_rest!($query, Ok);
+        try_rest!($query, Ok)
     );
     ($query:expr, $code:ident) => (
         {
             let response = match $query.send() {
                 Ok(response) => response,
-                Err(_) => return Err(Error::NetworkError),
+                Err(err) => return Err(err),
             };
             expect_code!(response, $code);
             response
         }
     );
 }
+
+// Future resolving to the response if it has the expected status code, see `try_rest!`.
+#[cfg(feature = "async")]
+macro_rules! try_rest_async {
+    ($query:expr) => (
+        try_rest_async!($query, Ok)
+    );
+    ($query:expr, $code:ident) => (
+        ::futures::future::TryFutureExt::and_then($query.send_async(), |response| {
+            ::futures::future::ready(if hyper::status::StatusCode::$code != response.status {
+                Err(::types::Error::from_response(response))
+            } else {
+                Ok(response)
+            })
+        })
+    );
+}
@@ -1,12 +1,25 @@
+use std::collections::HashMap;
 use std::io::Read;
 use rustc_serialize::{json, Encodable, Decodable};
 use hyper;
+use hyper::status::StatusCode;
+use url::form_urlencoded;
+use url::percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
+use client::RequestBuilder;
+use changes::{self, Changes, Snapshot};
+use cypher::{Cypher, CypherResultsResponse};
+use transport::Response;
+use retry::RetryPolicy;
 pub use types::Error;
+use types::{Neo4jError, ResponseError};
+
+#[cfg(feature = "async")] use futures::future::{self, BoxFuture, FutureExt, TryFutureExt};
 
 pub struct Node <T: Encodable = NodeUnidentifiedData> {
     id: Option<u64>,
     labels: Vec<String>,
     properties: Option<T>,
+    snapshot: Snapshot,
 }
 
 #[derive(RustcDecodable, RustcEncodable)]
@@ -24,24 +37,58 @@ struct NodeDataResponse<T: Decodable = NodeUnidentifiedData> {
 #[derive(RustcDecodable, RustcEncodable, Debug)]
 pub struct NodeUnidentifiedData;
 
+// Parameter of one node in add_many(), the labels are set by the statement per label group.
+#[derive(RustcEncodable)]
+struct BulkNodeParameter<'a, T: 'a> {
+    index: usize,
+    group: usize,
+    properties: Option<&'a T>,
+}
+
+#[derive(RustcDecodable)]
+struct BulkRow {
+    row: Vec<u64>,
+}
+
+// Outcome of deleting one node with delete_many().
+#[derive(Debug, PartialEq)]
+pub enum DeleteOutcome {
+    Deleted,
+    NotFound,
+    // The node is kept, see `detach_delete()`.
+    HasRelationships,
+}
+
 impl<T: Encodable + Decodable> Node<T> {
     pub fn new() -> Node<T> {
         Node {
             id: None,
             labels: Vec::new(),
             properties: None,
+            snapshot: Snapshot::new(),
         }
     }
 
     pub fn get(client: &::client::Client, id: u64) -> Result<Node<T>, Error> {
-        let path: String = format!("/db/data/node/{}", id);
-        let mut res = try_rest!(client.get(path), Ok);
+        let req = match Self::get_request(client, id) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        let res = try_rest!(req, Ok);
+        Self::from_response(res)
+    }
 
-        let mut res_raw = String::new();
-        let _ = res.read_to_string(&mut res_raw);
-        let node_json: NodeDataResponse<T> = match json::decode(&res_raw) {
-            Ok(res) => res,
-            Err(_) => return Err(Error::DataError),
+    fn get_request(client: &::client::Client, id: u64) -> Result<RequestBuilder<'_>, Error> {
+        match client.endpoint(|root| root.node_uri(id)) {
+            Ok(path) => Ok(client.get(path)),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn from_response(res: Response) -> Result<Node<T>, Error> {
+        let node_json = match read_node_json::<T>(res) {
+            Ok(node_json) => node_json,
+            Err(err) => return Err(err),
         };
 
         let mut node = Self::new();
@@ -50,6 +97,54 @@ impl<T: Encodable + Decodable> Node<T> {
         Ok(node)
     }
 
+    pub fn find_by_label(client: &::client::Client, label: &str) -> Result<Vec<Node<T>>, Error> {
+        let req = match Self::find_request(client, label, None) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        let res = try_rest!(req, Ok);
+        Self::from_find_response(res)
+    }
+
+    // Nodes with the label and the property equal to the value, eg. `find_by_label_and_property(&cli, "Person", "name", "Jane")`.
+    pub fn find_by_label_and_property<V: Encodable>(client: &::client::Client, label: &str, key: &str, value: V) -> Result<Vec<Node<T>>, Error> {
+        let value = match json::encode(&value) {
+            Ok(value) => value,
+            Err(err) => return Err(Error::from(err)),
+        };
+        let req = match Self::find_request(client, label, Some((key, value))) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        let res = try_rest!(req, Ok);
+        Self::from_find_response(res)
+    }
+
+    // The property value is sent JSON encoded, eg. ?name=%22Jane%22.
+    fn find_request<'c>(client: &'c ::client::Client, label: &str, property: Option<(&str, String)>) -> Result<RequestBuilder<'c>, Error> {
+        let path = match client.endpoint(|root| root.label_nodes_uri(label)) {
+            Ok(path) => path,
+            Err(err) => return Err(err),
+        };
+        let path = match property {
+            Some((key, value)) => format!("{}?{}", path, form_urlencoded::Serializer::new(String::new()).append_pair(key, &value).finish()),
+            None => path,
+        };
+        Ok(client.get(path))
+    }
+
+    fn from_find_response(res: Response) -> Result<Vec<Node<T>>, Error> {
+        let nodes_json: Vec<NodeDataResponse<T>> = match read_json(res) {
+            Ok(nodes_json) => nodes_json,
+            Err(err) => return Err(err),
+        };
+        Ok(nodes_json.into_iter().map(|node_json| {
+            let mut node = Self::new();
+            node.update_from_response_node_json(node_json);
+            node
+        }).collect())
+    }
+
     pub fn get_id(&self) -> Option<u64> {
         self.id
     }
@@ -68,6 +163,112 @@ impl<T: Encodable + Decodable> Node<T> {
         self.id = Some(node_json.metadata.id);
         self.labels = node_json.metadata.labels.clone();
         self.properties = Some(node_json.data);
+        // Properties that don't encode to an object make save() fail, there's nothing to diff them against.
+        self.snapshot = Snapshot::take(&self.properties, &self.labels).unwrap_or_default();
+    }
+
+    // Local changes are pushed with save(), see changes().
+    pub fn get_labels_mut(&mut self) -> &mut Vec<String> {
+        &mut self.labels
+    }
+
+    pub fn get_properties_mut(&mut self) -> Option<&mut T> {
+        self.properties.as_mut()
+    }
+
+    // Properties and labels changed locally since the node was last fetched, reloaded or saved.
+    pub fn changes(&self) -> Result<Changes, Error> {
+        self.snapshot.diff(&self.properties, &self.labels)
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        match self.changes() {
+            Ok(changes) => !changes.is_empty(),
+            Err(_) => true,
+        }
+    }
+
+    // Re-fetches the properties and labels, the local changes are lost.
+    pub fn reload(&mut self, client: &::client::Client) -> Result<(), Error> {
+        let req = match self.reload_request(client) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        let res = try_rest!(req, Ok);
+        match read_node_json::<T>(res) {
+            Ok(node_json) => {
+                self.update_from_response_node_json(node_json);
+                Ok(())
+            },
+            Err(err) => Err(err),
+        }
+    }
+
+    fn reload_request<'c>(&self, client: &'c ::client::Client) -> Result<RequestBuilder<'c>, Error> {
+        match self.get_id() {
+            Some(id) => Self::get_request(client, id),
+            None => Err(Error::IntegrityError),
+        }
+    }
+
+    // Pushes only the local changes, one request for each changed or removed property and removed label, one for the added labels.
+    // Properties changed on the server in the meantime are kept unless they were changed locally as well.
+    pub fn save(&mut self, client: &::client::Client) -> Result<(), Error> {
+        let reqs = match self.save_requests(client) {
+            Ok(reqs) => reqs,
+            Err(err) => return Err(err),
+        };
+        for (req, change) in reqs {
+            try_rest!(req, NoContent);
+            self.snapshot.apply(change);
+        }
+
+        info!("Node saved: {}", self.id.unwrap());
+        Ok(())
+    }
+
+    // Each request comes with the change it makes, so the snapshot can follow the requests that succeeded.
+    fn save_requests<'c>(&self, client: &'c ::client::Client) -> Result<Vec<(RequestBuilder<'c>, Changes)>, Error> {
+        if self.get_id().is_none() {
+            return Err(Error::IntegrityError);
+        }
+        let changes = match self.changes() {
+            Ok(changes) => changes,
+            Err(err) => return Err(err),
+        };
+
+        let mut reqs = Vec::new();
+        for (key, value) in changes.set_properties {
+            let req = match self.set_property_request(client, &key, &value) {
+                Ok(req) => req,
+                Err(err) => return Err(err),
+            };
+            let mut change = Changes::default();
+            change.set_properties.insert(key, value);
+            reqs.push((req, change));
+        }
+        for key in changes.removed_properties {
+            let path = match self.properties_path(client, Some(&key)) {
+                Ok(path) => path,
+                Err(err) => return Err(err),
+            };
+            reqs.push((client.delete(path), Changes { removed_properties: vec![key], ..Changes::default() }));
+        }
+        if !changes.added_labels.is_empty() {
+            let req = match self.add_labels_request(client, &changes.added_labels) {
+                Ok(req) => req,
+                Err(err) => return Err(err),
+            };
+            reqs.push((req, Changes { added_labels: changes.added_labels, ..Changes::default() }));
+        }
+        for label in changes.removed_labels {
+            let path = match self.labels_path(client, Some(&label)) {
+                Ok(path) => path,
+                Err(err) => return Err(err),
+            };
+            reqs.push((client.delete(path), Changes { removed_labels: vec![label], ..Changes::default() }));
+        }
+        Ok(reqs)
     }
 
     // TODO make it return the self
@@ -76,21 +277,34 @@ impl<T: Encodable + Decodable> Node<T> {
     }
 
     pub fn add(&mut self, client: &::client::Client) -> Result<(), Error> {
+        let req = match self.add_request(client) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        let res = try_rest!(req, Created);
+        self.add_response(res)
+    }
+
+    fn add_request<'c>(&self, client: &'c ::client::Client) -> Result<RequestBuilder<'c>, Error> {
         if self.get_id().is_some() {
             return Err(Error::IntegrityError);
         }
 
-        let mut response_raw = String::new();
         let props_string: String = match self.properties {
             Some(ref props) => json::encode(props).unwrap(),
             None => String::new(),
         };
 
-        let mut res = try_rest!(client.post("/db/data/node".to_string()).body(&props_string), Created);
-        let _ = res.read_to_string(&mut response_raw);
-        let node_json:NodeDataResponse<T> = match json::decode(&response_raw) {
-            Ok(s) => s,
-            _ => return Err(Error::DataError),
+        match client.endpoint(|root| root.node.clone()) {
+            Ok(path) => Ok(client.post(path).body(&props_string)),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn add_response(&mut self, res: Response) -> Result<(), Error> {
+        let node_json = match read_node_json::<T>(res) {
+            Ok(node_json) => node_json,
+            Err(err) => return Err(err),
         };
         self.update_from_response_node_json(node_json);
 
@@ -98,31 +312,693 @@ impl<T: Encodable + Decodable> Node<T> {
         Ok(())
     }
 
+    // Creates all nodes with one Cypher statement in a single request. The result has an entry for each node in the
+    // original order, nodes that already have an id are not sent and come back as IntegrityError.
+    // The request is not idempotent, only a failed connect is retried (see `add_many_policy()`).
+    pub fn add_many(client: &::client::Client, nodes: Vec<Node<T>>) -> Result<Vec<Result<Node<T>, Error>>, Error> {
+        if nodes.iter().all(|node| node.get_id().is_some()) {
+            return Ok(Self::from_add_many_rows(nodes, Vec::new()));
+        }
+        let res = match Self::add_many_statement(client, &nodes) {
+            Ok((statement, params)) => Cypher::query_with_policy::<_, Vec<BulkRow>>(client, &add_many_policy(client), statement, params),
+            Err(err) => return Err(err),
+        };
+        match res {
+            Ok(mut res) => Ok(Self::from_add_many_rows(nodes, res.results.pop().map(|result| result.data).unwrap_or_default())),
+            Err(err) => Err(err),
+        }
+    }
+
+    // Labels can't be query parameters, the nodes are grouped by their labels and each group gets its own SET clause.
+    fn add_many_statement<'a>(client: &::client::Client, nodes: &'a [Node<T>]) -> Result<(String, HashMap<String, Vec<BulkNodeParameter<'a, T>>>), Error> {
+        let mut groups: Vec<&Vec<String>> = Vec::new();
+        let mut params = Vec::new();
+        for (index, node) in nodes.iter().enumerate() {
+            if node.get_id().is_some() {
+                continue;
+            }
+            let group = match groups.iter().position(|labels| **labels == node.labels) {
+                Some(group) => group,
+                None => {
+                    groups.push(&node.labels);
+                    groups.len() - 1
+                },
+            };
+            params.push(BulkNodeParameter {
+                index: index,
+                group: group,
+                properties: node.properties.as_ref(),
+            });
+        }
+
+        let server_info = match client.server_info() {
+            Ok(server_info) => server_info,
+            Err(err) => return Err(err),
+        };
+        let mut statement = format!("UNWIND {} AS node CREATE (n) SET n += coalesce(node.properties, {{}})", server_info.parameter("nodes"));
+        for (group, labels) in groups.iter().enumerate() {
+            if labels.is_empty() {
+                continue;
+            }
+            let labels: String = labels.iter().map(|label| format!(":`{}`", label.replace("`", "``"))).collect();
+            statement.push_str(&format!(" FOREACH (_ IN CASE WHEN node.group = {} THEN [1] ELSE [] END | SET n{})", group, labels));
+        }
+        statement.push_str(" RETURN node.index, id(n)");
+
+        let mut parameters = HashMap::new();
+        parameters.insert("nodes".to_string(), params);
+        Ok((statement, parameters))
+    }
+
+    // Each row is the index of a created node and its new id.
+    fn from_add_many_rows(nodes: Vec<Node<T>>, rows: Vec<BulkRow>) -> Vec<Result<Node<T>, Error>> {
+        let ids: HashMap<u64, u64> = rows.into_iter()
+            .filter(|row| row.row.len() == 2)
+            .map(|row| (row.row[0], row.row[1]))
+            .collect();
+
+        nodes.into_iter().enumerate().map(|(index, mut node)| {
+            if node.get_id().is_some() {
+                return Err(Error::IntegrityError);
+            }
+            match ids.get(&(index as u64)) {
+                Some(&id) => {
+                    node.id = Some(id);
+                    node.snapshot = Snapshot::take(&node.properties, &node.labels).unwrap_or_default();
+                    Ok(node)
+                },
+                None => Err(Error::DataError(From::from(format!("No id returned for node {}", index)))),
+            }
+        }).collect()
+    }
+
+    // The label calls keep the local labels in sync with the server after each successful call.
     pub fn add_labels(&mut self, client: &::client::Client, labels: Vec<String>) -> Result<(), Error> {
-        if self.get_id().is_none() {
-            return Err(Error::IntegrityError);
+        let req = match self.add_labels_request(client, &labels) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        try_rest!(req, NoContent);
+
+        info!("Labels {:?} added to {}", labels, self.id.unwrap());
+        self.merge_labels(labels);
+        Ok(())
+    }
+
+    fn add_labels_request<'c>(&self, client: &'c ::client::Client, labels: &[String]) -> Result<RequestBuilder<'c>, Error> {
+        let labels_raw = match json::encode(&labels) {
+            Ok(s) => s,
+            Err(err) => return Err(Error::from(err)),
+        };
+        match self.labels_path(client, None) {
+            Ok(path) => Ok(client.post(path).body(&labels_raw)),
+            Err(err) => Err(err),
         }
+    }
 
-        let labels_raw:String = ["[\"", &*labels.join("\", \""), "\"]"].concat();
-        let path:String = format!("/db/data/node/{}/labels", self.id.unwrap());
+    fn merge_labels(&mut self, labels: Vec<String>) {
+        for label in labels {
+            self.snapshot.add_label(label.clone());
+            if !self.labels.contains(&label) {
+                self.labels.push(label);
+            }
+        }
+    }
 
-        try_rest!(client.post(path).body(&*labels_raw), NoContent);
+    // Replaces all labels of the node.
+    pub fn set_labels(&mut self, client: &::client::Client, labels: Vec<String>) -> Result<(), Error> {
+        let req = match self.set_labels_request(client, &labels) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        try_rest!(req, NoContent);
 
-        info!("Labels {:?} added to {}", labels_raw, self.id.unwrap());
+        info!("Labels of {} set to {:?}", self.id.unwrap(), labels);
+        self.snapshot.set_labels(labels.clone());
+        self.labels = labels;
         Ok(())
     }
 
+    fn set_labels_request<'c>(&self, client: &'c ::client::Client, labels: &[String]) -> Result<RequestBuilder<'c>, Error> {
+        let labels_raw = match json::encode(&labels) {
+            Ok(s) => s,
+            Err(err) => return Err(Error::from(err)),
+        };
+        match self.labels_path(client, None) {
+            Ok(path) => Ok(client.put(path).body(&labels_raw)),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn remove_label(&mut self, client: &::client::Client, label: String) -> Result<(), Error> {
+        let path = match self.labels_path(client, Some(&label)) {
+            Ok(path) => path,
+            Err(err) => return Err(err),
+        };
+        try_rest!(client.delete(path), NoContent);
+
+        info!("Label {} removed from {}", label, self.id.unwrap());
+        self.snapshot.remove_label(&label);
+        self.labels.retain(|l| *l != label);
+        Ok(())
+    }
+
+    // Reloads the labels from the server.
+    pub fn fetch_labels(&mut self, client: &::client::Client) -> Result<(), Error> {
+        let path = match self.labels_path(client, None) {
+            Ok(path) => path,
+            Err(err) => return Err(err),
+        };
+        let res = try_rest!(client.get(path), Ok);
+        match read_json::<Vec<String>>(res) {
+            Ok(labels) => {
+                self.snapshot.set_labels(labels.clone());
+                self.labels = labels;
+                Ok(())
+            },
+            Err(err) => Err(err),
+        }
+    }
+
+    fn labels_path(&self, client: &::client::Client, label: Option<&str>) -> Result<String, Error> {
+        let id = match self.get_id() {
+            Some(id) => id,
+            None => return Err(Error::IntegrityError),
+        };
+        client.endpoint(|root| match label {
+            Some(label) => format!("{}/labels/{}", root.node_uri(id), utf8_percent_encode(label, PATH_SEGMENT_ENCODE_SET)),
+            None => format!("{}/labels", root.node_uri(id)),
+        })
+    }
+
     pub fn delete(self, client: &::client::Client) -> Result<(), Error> {
+        let req = match self.delete_request(client) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        let res = match req.send() {
+            Ok(res) => res,
+            Err(err) => return Err(err),
+        };
+        if let Err(err) = from_delete_response(res) {
+            return Err(err);
+        }
+
+        info!("Node deleted: {}", self.id.unwrap());
+        Ok(())
+    }
+
+    // Deletes the node with all of its relationships in one Cypher statement. A missing node is a NotFound
+    // response error, the same as for `delete()`.
+    pub fn detach_delete(self, client: &::client::Client) -> Result<(), Error> {
+        let (statement, params) = match self.detach_delete_statement(client) {
+            Ok(statement) => statement,
+            Err(err) => return Err(err),
+        };
+        let id = self.id.unwrap();
+        match Cypher::query::<HashMap<String, u64>, Vec<BulkRow>>(client, statement, params) {
+            Ok(res) => match from_detach_delete_response(id, res) {
+                Ok(_) => {},
+                Err(err) => return Err(err),
+            },
+            Err(err) => return Err(err),
+        }
+
+        info!("Node deleted with its relationships: {}", self.id.unwrap());
+        Ok(())
+    }
+
+    fn detach_delete_statement(&self, client: &::client::Client) -> Result<(String, HashMap<String, u64>), Error> {
+        let id = match self.get_id() {
+            Some(id) => id,
+            None => return Err(Error::IntegrityError),
+        };
+        let server_info = match client.server_info() {
+            Ok(server_info) => server_info,
+            Err(err) => return Err(err),
+        };
+
+        let mut params = HashMap::new();
+        params.insert("id".to_string(), id);
+        Ok((format!("MATCH (n) WHERE id(n) = {} DETACH DELETE n RETURN count(n)", server_info.parameter("id")), params))
+    }
+
+    fn delete_request<'c>(&self, client: &'c ::client::Client) -> Result<RequestBuilder<'c>, Error> {
         if self.get_id().is_none() {
             return Err(Error::IntegrityError);
         }
 
-        let path:String = format!("/db/data/node/{}", self.get_id().unwrap());
+        let id = self.get_id().unwrap();
+        match client.endpoint(|root| root.node_uri(id)) {
+            Ok(path) => Ok(client.delete(path)),
+            Err(err) => Err(err),
+        }
+    }
+
+    // The calls changing properties below keep the local properties (and the snapshot of save()) in sync after each
+    // successful call.
+
+    // Replaces all properties on the server with the local ones.
+    pub fn update_properties(&mut self, client: &::client::Client) -> Result<(), Error> {
+        let req = match self.update_properties_request(client) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        try_rest!(req, NoContent);
+        self.properties_saved();
+        Ok(())
+    }
+
+    fn update_properties_request<'c>(&self, client: &'c ::client::Client) -> Result<RequestBuilder<'c>, Error> {
+        let props_string = match self.properties {
+            Some(ref props) => match json::encode(props) {
+                Ok(s) => s,
+                Err(err) => return Err(Error::from(err)),
+            },
+            None => "{}".to_string(),
+        };
+        match self.properties_path(client, None) {
+            Ok(path) => Ok(client.put(path).body(&props_string)),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn set_property<V: Encodable>(&mut self, client: &::client::Client, key: String, value: V) -> Result<(), Error> {
+        let value = match changes::encode_value(&value) {
+            Ok(value) => value,
+            Err(err) => return Err(err),
+        };
+        let req = match self.set_property_request(client, &key, &value) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        try_rest!(req, NoContent);
+        self.update_property(&key, Some(value));
+        Ok(())
+    }
+
+    fn set_property_request<'c, V: Encodable>(&self, client: &'c ::client::Client, key: &str, value: V) -> Result<RequestBuilder<'c>, Error> {
+        let value = match json::encode(&value) {
+            Ok(s) => s,
+            Err(err) => return Err(Error::from(err)),
+        };
+        match self.properties_path(client, Some(key)) {
+            Ok(path) => Ok(client.put(path).body(&value)),
+            Err(err) => Err(err),
+        }
+    }
+
+    // A missing property is a NotFound response error.
+    pub fn get_property<V: Decodable>(&self, client: &::client::Client, key: String) -> Result<V, Error> {
+        let path = match self.properties_path(client, Some(&key)) {
+            Ok(path) => path,
+            Err(err) => return Err(err),
+        };
+        let res = try_rest!(client.get(path), Ok);
+        read_json(res)
+    }
+
+    pub fn remove_property(&mut self, client: &::client::Client, key: String) -> Result<(), Error> {
+        let path = match self.properties_path(client, Some(&key)) {
+            Ok(path) => path,
+            Err(err) => return Err(err),
+        };
         try_rest!(client.delete(path), NoContent);
+        self.update_property(&key, None);
+        Ok(())
+    }
 
-        info!("Node deleted: {}", self.id.unwrap());
+    // Removes all properties, the local ones included.
+    pub fn clear_properties(&mut self, client: &::client::Client) -> Result<(), Error> {
+        let path = match self.properties_path(client, None) {
+            Ok(path) => path,
+            Err(err) => return Err(err),
+        };
+        try_rest!(client.delete(path), NoContent);
+        self.snapshot.clear_properties();
+        self.properties = None;
         Ok(())
     }
+
+    fn properties_saved(&mut self) {
+        if let Err(err) = self.snapshot.set_properties(&self.properties) {
+            warn!("Snapshot of node {} not updated: {}", self.id.unwrap_or_default(), err);
+        }
+    }
+
+    fn update_property(&mut self, key: &str, value: Option<json::Json>) {
+        if let Err(err) = changes::update_property(&mut self.properties, &mut self.snapshot, key, value) {
+            warn!("Local properties of node {} not updated: {}", self.id.unwrap_or_default(), err);
+        }
+    }
+
+    fn properties_path(&self, client: &::client::Client, key: Option<&str>) -> Result<String, Error> {
+        let id = match self.get_id() {
+            Some(id) => id,
+            None => return Err(Error::IntegrityError),
+        };
+        client.endpoint(|root| match key {
+            Some(key) => format!("{}/properties/{}", root.node_uri(id), utf8_percent_encode(key, PATH_SEGMENT_ENCODE_SET)),
+            None => format!("{}/properties", root.node_uri(id)),
+        })
+    }
+}
+
+// Non-blocking variants, they share the request building and response handling with the blocking calls above.
+#[cfg(feature = "async")]
+impl<T: Encodable + Decodable + Send + 'static> Node<T> {
+    pub fn get_async(client: &::client::Client, id: u64) -> BoxFuture<'static, Result<Node<T>, Error>> {
+        let req = match Self::get_request(client, id) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(req, Ok)
+            .map(|res| res.and_then(Self::from_response))
+            .boxed()
+    }
+
+    pub fn find_by_label_async(client: &::client::Client, label: &str) -> BoxFuture<'static, Result<Vec<Node<T>>, Error>> {
+        let req = match Self::find_request(client, label, None) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(req, Ok)
+            .map(|res| res.and_then(Self::from_find_response))
+            .boxed()
+    }
+
+    pub fn find_by_label_and_property_async<V: Encodable>(client: &::client::Client, label: &str, key: &str, value: V) -> BoxFuture<'static, Result<Vec<Node<T>>, Error>> {
+        let value = match json::encode(&value) {
+            Ok(value) => value,
+            Err(err) => return future::ready(Err(Error::from(err))).boxed(),
+        };
+        let req = match Self::find_request(client, label, Some((key, value))) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(req, Ok)
+            .map(|res| res.and_then(Self::from_find_response))
+            .boxed()
+    }
+
+    pub fn add_async<'a>(&'a mut self, client: &::client::Client) -> BoxFuture<'a, Result<(), Error>> {
+        let req = match self.add_request(client) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(req, Created)
+            .map(move |res| res.and_then(|res| self.add_response(res)))
+            .boxed()
+    }
+
+    pub fn reload_async<'a>(&'a mut self, client: &::client::Client) -> BoxFuture<'a, Result<(), Error>> {
+        let req = match self.reload_request(client) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(req, Ok)
+            .map(move |res| res.and_then(read_node_json).map(|node_json| self.update_from_response_node_json(node_json)))
+            .boxed()
+    }
+
+    // Unlike save() the requests are sent concurrently, the snapshot is only updated when all of them succeed.
+    pub fn save_async<'a>(&'a mut self, client: &::client::Client) -> BoxFuture<'a, Result<(), Error>> {
+        let reqs = match self.save_requests(client) {
+            Ok(reqs) => reqs,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        let (futures, changes): (Vec<_>, Vec<_>) = reqs.into_iter()
+            .map(|(req, change)| (try_rest_async!(req, NoContent), change))
+            .unzip();
+        future::try_join_all(futures)
+            .map_ok(move |_| {
+                for change in changes {
+                    self.snapshot.apply(change);
+                }
+                info!("Node saved: {}", self.id.unwrap());
+            })
+            .boxed()
+    }
+
+    pub fn add_labels_async<'a>(&'a mut self, client: &::client::Client, labels: Vec<String>) -> BoxFuture<'a, Result<(), Error>> {
+        let req = match self.add_labels_request(client, &labels) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        let id = self.id.unwrap();
+        try_rest_async!(req, NoContent)
+            .map_ok(move |_| {
+                info!("Labels {:?} added to {}", labels, id);
+                self.merge_labels(labels);
+            })
+            .boxed()
+    }
+
+    pub fn set_labels_async<'a>(&'a mut self, client: &::client::Client, labels: Vec<String>) -> BoxFuture<'a, Result<(), Error>> {
+        let req = match self.set_labels_request(client, &labels) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(req, NoContent)
+            .map_ok(move |_| {
+                info!("Labels of {} set to {:?}", self.id.unwrap(), labels);
+                self.snapshot.set_labels(labels.clone());
+                self.labels = labels;
+            })
+            .boxed()
+    }
+
+    pub fn remove_label_async<'a>(&'a mut self, client: &::client::Client, label: String) -> BoxFuture<'a, Result<(), Error>> {
+        let path = match self.labels_path(client, Some(&label)) {
+            Ok(path) => path,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(client.delete(path), NoContent)
+            .map_ok(move |_| {
+                info!("Label {} removed from {}", label, self.id.unwrap());
+                self.snapshot.remove_label(&label);
+                self.labels.retain(|l| *l != label);
+            })
+            .boxed()
+    }
+
+    pub fn fetch_labels_async<'a>(&'a mut self, client: &::client::Client) -> BoxFuture<'a, Result<(), Error>> {
+        let path = match self.labels_path(client, None) {
+            Ok(path) => path,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(client.get(path), Ok)
+            .map(move |res| res.and_then(read_json).map(|labels: Vec<String>| {
+                self.snapshot.set_labels(labels.clone());
+                self.labels = labels;
+            }))
+            .boxed()
+    }
+
+    pub fn add_many_async(client: &::client::Client, nodes: Vec<Node<T>>) -> BoxFuture<'static, Result<Vec<Result<Node<T>, Error>>, Error>> {
+        if nodes.iter().all(|node| node.get_id().is_some()) {
+            return future::ready(Ok(Self::from_add_many_rows(nodes, Vec::new()))).boxed();
+        }
+        let query = match Self::add_many_statement(client, &nodes) {
+            Ok((statement, params)) => Cypher::query_with_policy_async::<_, Vec<BulkRow>>(client, &add_many_policy(client), statement, params),
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        query
+            .map_ok(move |mut res| Self::from_add_many_rows(nodes, res.results.pop().map(|result| result.data).unwrap_or_default()))
+            .boxed()
+    }
+
+    pub fn delete_async(self, client: &::client::Client) -> BoxFuture<'static, Result<(), Error>> {
+        let req = match self.delete_request(client) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        let id = self.id.unwrap();
+        req.send_async()
+            .map(|res| res.and_then(from_delete_response))
+            .map_ok(move |_| info!("Node deleted: {}", id))
+            .boxed()
+    }
+
+    pub fn detach_delete_async(self, client: &::client::Client) -> BoxFuture<'static, Result<(), Error>> {
+        let (statement, params) = match self.detach_delete_statement(client) {
+            Ok(statement) => statement,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        let id = self.id.unwrap();
+        Cypher::query_async::<HashMap<String, u64>, Vec<BulkRow>>(client, statement, params)
+            .map(move |res| res.and_then(|res| from_detach_delete_response(id, res)))
+            .map_ok(move |_| info!("Node deleted with its relationships: {}", id))
+            .boxed()
+    }
+
+    pub fn update_properties_async<'a>(&'a mut self, client: &::client::Client) -> BoxFuture<'a, Result<(), Error>> {
+        let req = match self.update_properties_request(client) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(req, NoContent)
+            .map_ok(move |_| self.properties_saved())
+            .boxed()
+    }
+
+    pub fn set_property_async<'a, V: Encodable>(&'a mut self, client: &::client::Client, key: String, value: V) -> BoxFuture<'a, Result<(), Error>> {
+        let value = match changes::encode_value(&value) {
+            Ok(value) => value,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        let req = match self.set_property_request(client, &key, &value) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(req, NoContent)
+            .map_ok(move |_| self.update_property(&key, Some(value)))
+            .boxed()
+    }
+
+    pub fn get_property_async<V: Decodable + Send + 'static>(&self, client: &::client::Client, key: String) -> BoxFuture<'static, Result<V, Error>> {
+        let path = match self.properties_path(client, Some(&key)) {
+            Ok(path) => path,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(client.get(path), Ok)
+            .map(|res| res.and_then(read_json))
+            .boxed()
+    }
+
+    pub fn remove_property_async<'a>(&'a mut self, client: &::client::Client, key: String) -> BoxFuture<'a, Result<(), Error>> {
+        let path = match self.properties_path(client, Some(&key)) {
+            Ok(path) => path,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(client.delete(path), NoContent)
+            .map_ok(move |_| self.update_property(&key, None))
+            .boxed()
+    }
+
+    pub fn clear_properties_async<'a>(&'a mut self, client: &::client::Client) -> BoxFuture<'a, Result<(), Error>> {
+        let path = match self.properties_path(client, None) {
+            Ok(path) => path,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(client.delete(path), NoContent)
+            .map_ok(move |_| {
+                self.snapshot.clear_properties();
+                self.properties = None;
+            })
+            .boxed()
+    }
+}
+
+// Deleting by id doesn't need the type of the properties.
+impl Node {
+    // Deletes the nodes with one Cypher statement in a single request, the outcomes follow the order of the ids.
+    // Nodes that still have relationships are kept, the rest is deleted anyway.
+    pub fn delete_many(client: &::client::Client, ids: Vec<u64>) -> Result<Vec<DeleteOutcome>, Error> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let (statement, params) = match Self::delete_many_statement(client, &ids) {
+            Ok(statement) => statement,
+            Err(err) => return Err(err),
+        };
+        match Cypher::query::<_, Vec<BulkRow>>(client, statement, params) {
+            Ok(mut res) => Ok(from_delete_many_rows(&ids, res.results.pop().map(|result| result.data).unwrap_or_default())),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn delete_many_statement(client: &::client::Client, ids: &[u64]) -> Result<(String, HashMap<String, Vec<u64>>), Error> {
+        let server_info = match client.server_info() {
+            Ok(server_info) => server_info,
+            Err(err) => return Err(err),
+        };
+
+        let mut params = HashMap::new();
+        params.insert("ids".to_string(), ids.to_vec());
+        Ok((format!("UNWIND {} AS node_id MATCH (n) WHERE id(n) = node_id \
+            OPTIONAL MATCH (n)-[r]-() WITH node_id, n, count(r) AS relationships \
+            FOREACH (_ IN CASE WHEN relationships = 0 THEN [1] ELSE [] END | DELETE n) \
+            RETURN node_id, relationships", server_info.parameter("ids")), params))
+    }
+}
+
+#[cfg(feature = "async")]
+impl Node {
+    pub fn delete_many_async(client: &::client::Client, ids: Vec<u64>) -> BoxFuture<'static, Result<Vec<DeleteOutcome>, Error>> {
+        if ids.is_empty() {
+            return future::ready(Ok(Vec::new())).boxed();
+        }
+        let (statement, params) = match Self::delete_many_statement(client, &ids) {
+            Ok(statement) => statement,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        Cypher::query_async::<_, Vec<BulkRow>>(client, statement, params)
+            .map_ok(move |mut res| from_delete_many_rows(&ids, res.results.pop().map(|result| result.data).unwrap_or_default()))
+            .boxed()
+    }
+}
+
+// The server answers 409 when the node still has relationships.
+fn from_delete_response(res: Response) -> Result<(), Error> {
+    match res.status {
+        StatusCode::NoContent => Ok(()),
+        StatusCode::Conflict => Err(Error::NodeHasRelationships(ResponseError::from_response(res))),
+        _ => Err(Error::from_response(res)),
+    }
+}
+
+// Any other error may come after the nodes were created, sending the statement again would create them twice.
+fn add_many_policy(client: &::client::Client) -> RetryPolicy {
+    client.get_retry_policy().clone().retryable(|err| err.is_connect_failure())
+}
+
+// The single row is the number of deleted nodes, none when the id is not found.
+fn from_detach_delete_response(id: u64, res: CypherResultsResponse<Vec<BulkRow>>) -> Result<(), Error> {
+    let deleted = res.results.first()
+        .and_then(|result| result.data.first())
+        .and_then(|row| row.row.first())
+        .cloned()
+        .unwrap_or(0);
+    if deleted > 0 {
+        return Ok(());
+    }
+
+    Err(Error::ResponseError(ResponseError {
+        status: StatusCode::NotFound,
+        body: String::new(),
+        errors: vec![Neo4jError {
+            code: "Neo.ClientError.Statement.EntityNotFound".to_string(),
+            message: format!("Node[{}] not found", id),
+        }],
+    }))
+}
+
+// Each row is the id of a found node and its number of relationships, missing ids were not found.
+fn from_delete_many_rows(ids: &[u64], rows: Vec<BulkRow>) -> Vec<DeleteOutcome> {
+    let relationships: HashMap<u64, u64> = rows.into_iter()
+        .filter(|row| row.row.len() == 2)
+        .map(|row| (row.row[0], row.row[1]))
+        .collect();
+
+    ids.iter().map(|id| match relationships.get(id) {
+        Some(&0) => DeleteOutcome::Deleted,
+        Some(_) => DeleteOutcome::HasRelationships,
+        None => DeleteOutcome::NotFound,
+    }).collect()
+}
+
+fn read_node_json<T: Decodable>(res: Response) -> Result<NodeDataResponse<T>, Error> {
+    read_json(res)
+}
+
+fn read_json<V: Decodable>(mut res: Response) -> Result<V, Error> {
+    let mut res_raw = String::new();
+    let _ = res.read_to_string(&mut res_raw);
+    match json::decode(&res_raw) {
+        Ok(value) => Ok(value),
+        Err(err) => Err(Error::from(err)),
+    }
 }
 
 #[cfg(test)]
@@ -130,6 +1006,9 @@ mod tests {
     use std::env;
     use client;
     use node;
+    use hyper::method::Method;
+    use hyper::status::StatusCode;
+    use transport::MockTransport;
 
     #[derive(RustcEncodable, RustcDecodable)]
     struct TestNodeData {
@@ -148,6 +1027,15 @@ mod tests {
             .get()
     }
 
+    fn get_mock_client(transport: &MockTransport) -> ::client::Client {
+        let builder = client::ClientBuilder::new()
+            .discovery(false)
+            .transport(transport.clone());
+        #[cfg(feature = "async")]
+        let builder = builder.async_transport(transport.clone());
+        builder.get()
+    }
+
     #[test]
     pub fn test_node_create_no_type() {
         let cli = get_client();
@@ -200,5 +1088,394 @@ mod tests {
 
         assert!(node.delete(&cli).is_ok());
     }
+    #[test]
+    pub fn test_node_create_and_get_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Created, r#"{"metadata":{"id":17,"labels":[]},"data":{"name":"John Doe","level":-42}}"#);
+        transport.push_response(StatusCode::Ok, r#"{"metadata":{"id":17,"labels":["foo"]},"data":{"name":"John Doe","level":-42}}"#);
+        let cli = get_mock_client(&transport);
+
+        let mut node: node::Node<TestNodeData> = node::Node::new();
+        node.set_properties(TestNodeData { name: "John Doe".to_string(), level: -42 });
+        assert!(node.add(&cli).is_ok());
+        assert_eq!(Some(17), node.get_id());
+
+        let node_reload: node::Node<TestNodeData> = node::Node::get(&cli, 17).unwrap();
+        assert_eq!(node_reload.labels, vec!["foo".to_string()]);
+        assert_eq!(node_reload.properties.as_ref().unwrap().name, "John Doe");
+
+        let requests = transport.requests();
+        assert_eq!(Method::Post, requests[0].method);
+        assert_eq!("http://localhost:7474/db/data/node", requests[0].uri);
+        assert_eq!(Some(r#"{"name":"John Doe","level":-42}"#.to_string()), requests[0].body);
+        assert_eq!(Method::Get, requests[1].method);
+        assert_eq!("http://localhost:7474/db/data/node/17", requests[1].uri);
+    }
 
-}
\ No newline at end of file
+    #[test]
+    pub fn test_node_delete_with_mock_fails_on_unexpected_status() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Conflict, "");
+        let cli = get_mock_client(&transport);
+
+        let mut node: node::Node = node::Node::new();
+        node.id = Some(17);
+        assert_eq!(Some(StatusCode::Conflict), node.delete(&cli).unwrap_err().status());
+        assert_eq!(Method::Delete, transport.requests()[0].method);
+    }
+
+    #[test]
+    pub fn test_node_detach_delete_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Conflict, r#"{"errors":[{"code":"Neo.ClientError.Schema.ConstraintValidationFailed","message":"The node with id 17 cannot be deleted. Check that the node is orphaned before deletion."}]}"#);
+        transport.push_response(StatusCode::Ok, r#"{"results":[{"columns":["count(n)"],"data":[{"row":[1]}]}],"errors":[]}"#);
+        transport.push_response(StatusCode::Ok, r#"{"results":[{"columns":["count(n)"],"data":[{"row":[0]}]}],"errors":[]}"#);
+        let cli = get_mock_client(&transport);
+
+        let mut node: node::Node = node::Node::new();
+        node.id = Some(17);
+        match node.delete(&cli) {
+            Err(node::Error::NodeHasRelationships(ref err)) => assert_eq!(1, err.errors.len()),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let mut node: node::Node = node::Node::new();
+        node.id = Some(17);
+        assert!(node.detach_delete(&cli).is_ok());
+        let mut node: node::Node = node::Node::new();
+        node.id = Some(17);
+        assert_eq!(Some(StatusCode::NotFound), node.detach_delete(&cli).unwrap_err().status());
+        match node::Node::<node::NodeUnidentifiedData>::new().detach_delete(&cli) {
+            Err(node::Error::IntegrityError) => {},
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let requests = transport.requests();
+        assert_eq!(3, requests.len());
+        assert_eq!(Method::Post, requests[1].method);
+        assert_eq!("http://localhost:7474/db/data/transaction/commit", requests[1].uri);
+        assert_eq!(Some(r#"{"statements":[{"statement":"MATCH (n) WHERE id(n) = $id DETACH DELETE n RETURN count(n)","parameters":{"id":17}}]}"#.to_string()), requests[1].body);
+    }
+
+    #[test]
+    pub fn test_node_add_and_delete_many_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Ok, r#"{"results":[{"columns":["node.index","id(n)"],"data":[{"row":[0,20]},{"row":[2,21]}]}],"errors":[]}"#);
+        transport.push_response(StatusCode::Ok, r#"{"results":[{"columns":["node_id","relationships"],"data":[{"row":[20,0]},{"row":[21,3]}]}],"errors":[]}"#);
+        let cli = get_mock_client(&transport);
+
+        let mut nodes: Vec<node::Node<TestNodeData>> = (0..3).map(|level| {
+            let mut node = node::Node::new();
+            node.set_properties(TestNodeData { name: "John Doe".to_string(), level: level });
+            node
+        }).collect();
+        nodes[0].labels.push("Person".to_string());
+        nodes[1].id = Some(5);
+
+        let res = node::Node::add_many(&cli, nodes).unwrap();
+        assert_eq!(3, res.len());
+        assert_eq!(Some(20), res[0].as_ref().unwrap().get_id());
+        assert!(!res[0].as_ref().unwrap().is_dirty());
+        assert!(match res[1] { Err(node::Error::IntegrityError) => true, _ => false });
+        assert_eq!(Some(21), res[2].as_ref().unwrap().get_id());
+
+        let outcomes = node::Node::delete_many(&cli, vec![20, 21, 22]).unwrap();
+        assert_eq!(vec![node::DeleteOutcome::Deleted, node::DeleteOutcome::HasRelationships, node::DeleteOutcome::NotFound], outcomes);
+        assert!(node::Node::delete_many(&cli, vec![]).unwrap().is_empty());
+
+        let requests = transport.requests();
+        assert_eq!(2, requests.len());
+        assert_eq!("http://localhost:7474/db/data/transaction/commit", requests[0].uri);
+        assert_eq!(Some(r#"{"statements":[{"statement":"UNWIND $nodes AS node CREATE (n) SET n += coalesce(node.properties, {}) FOREACH (_ IN CASE WHEN node.group = 0 THEN [1] ELSE [] END | SET n:`Person`) RETURN node.index, id(n)","parameters":{"nodes":[{"index":0,"group":0,"properties":{"name":"John Doe","level":0}},{"index":2,"group":1,"properties":{"name":"John Doe","level":2}}]}}]}"#.to_string()), requests[0].body);
+        assert!(requests[1].body.as_ref().unwrap().contains(r#""parameters":{"ids":[20,21,22]}"#));
+    }
+
+    #[test]
+    pub fn test_node_add_many_retries_only_connect_failures_with_mock() {
+        use std::io;
+        use std::time::Duration;
+        use retry::RetryPolicy;
+
+        let transport = MockTransport::new();
+        transport.push_error(node::Error::from_connect_failure(io::Error::new(io::ErrorKind::ConnectionRefused, "refused")));
+        transport.push_response(StatusCode::Ok, r#"{"results":[{"columns":["node.index","id(n)"],"data":[{"row":[0,20]}]}],"errors":[]}"#);
+        transport.push_response(StatusCode::Ok, r#"{"results":[],"errors":[{"code":"Neo.TransientError.Transaction.DeadlockDetected","message":"Deadlock"}]}"#);
+        transport.push_error(node::Error::from(io::Error::new(io::ErrorKind::ConnectionReset, "reset")));
+        let builder = client::ClientBuilder::new()
+            .discovery(false)
+            .transport(transport.clone())
+            .retry_policy(RetryPolicy::new().max_attempts(3).initial_backoff(Duration::from_millis(0)));
+        #[cfg(feature = "async")]
+        let builder = builder.async_transport(transport.clone());
+        let cli = builder.get();
+
+        let res = node::Node::add_many(&cli, vec![node::Node::<TestNodeData>::new()]).unwrap();
+        assert_eq!(Some(20), res[0].as_ref().unwrap().get_id());
+        assert_eq!(2, transport.requests().len());
+
+        // The nodes may have been created, neither error sends the statement again.
+        match node::Node::add_many(&cli, vec![node::Node::<TestNodeData>::new()]) {
+            Err(ref err) if err.is_transient() => {},
+            _ => panic!("Expected transient error"),
+        }
+        assert_eq!(3, transport.requests().len());
+        assert!(node::Node::add_many(&cli, vec![node::Node::<TestNodeData>::new()]).is_err());
+        assert_eq!(4, transport.requests().len());
+    }
+
+    #[test]
+    pub fn test_node_properties_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::NoContent, "");
+        transport.push_response(StatusCode::NoContent, "");
+        transport.push_response(StatusCode::Ok, r#""Jane Doe""#);
+        transport.push_response(StatusCode::NotFound, r#"{"errors":[{"code":"Neo.ClientError.Statement.EntityNotFound","message":"Node[17] has no property with propertyKey=\"nickname\"."}]}"#);
+        transport.push_response(StatusCode::NoContent, "");
+        transport.push_response(StatusCode::NoContent, "");
+        let cli = get_mock_client(&transport);
+
+        let mut node: node::Node<TestNodeData> = node::Node::new();
+        match node.set_property(&cli, "name".to_string(), "Jane Doe") {
+            Err(node::Error::IntegrityError) => {},
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        node.id = Some(17);
+        node.set_properties(TestNodeData { name: "John Doe".to_string(), level: 3 });
+        assert!(node.is_dirty());
+
+        assert!(node.update_properties(&cli).is_ok());
+        assert!(!node.is_dirty());
+        assert!(node.set_property(&cli, "name".to_string(), "Jane Doe").is_ok());
+        assert_eq!("Jane Doe", node.get_properties().as_ref().unwrap().name);
+        assert!(!node.is_dirty());
+        assert_eq!("Jane Doe", node.get_property::<String>(&cli, "name".to_string()).unwrap());
+        assert_eq!(Some(StatusCode::NotFound), node.get_property::<String>(&cli, "nickname".to_string()).unwrap_err().status());
+        assert!(node.remove_property(&cli, "home town".to_string()).is_ok());
+        assert!(!node.is_dirty());
+        assert!(node.clear_properties(&cli).is_ok());
+        assert!(node.get_properties().is_none());
+        assert!(!node.is_dirty());
+
+        let requests = transport.requests();
+        assert_eq!(0, transport.pending_responses());
+        assert_eq!(Method::Put, requests[0].method);
+        assert_eq!("http://localhost:7474/db/data/node/17/properties", requests[0].uri);
+        assert_eq!(Some(r#"{"name":"John Doe","level":3}"#.to_string()), requests[0].body);
+        assert_eq!(Method::Put, requests[1].method);
+        assert_eq!("http://localhost:7474/db/data/node/17/properties/name", requests[1].uri);
+        assert_eq!(Some(r#""Jane Doe""#.to_string()), requests[1].body);
+        assert_eq!(Method::Get, requests[2].method);
+        assert_eq!(Method::Delete, requests[4].method);
+        assert_eq!("http://localhost:7474/db/data/node/17/properties/home%20town", requests[4].uri);
+        assert_eq!(Method::Delete, requests[5].method);
+        assert_eq!("http://localhost:7474/db/data/node/17/properties", requests[5].uri);
+    }
+
+    #[test]
+    pub fn test_node_reload_and_save_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Ok, r#"{"metadata":{"id":17,"labels":["Person"]},"data":{"name":"John Doe","level":-42}}"#);
+        for _ in 0..3 {
+            transport.push_response(StatusCode::NoContent, "");
+        }
+        transport.push_response(StatusCode::Ok, r#"{"metadata":{"id":17,"labels":["Admin"]},"data":{"name":"Jane Doe","level":3}}"#);
+        let cli = get_mock_client(&transport);
+
+        let mut node: node::Node<TestNodeData> = node::Node::get(&cli, 17).unwrap();
+        assert!(!node.is_dirty());
+        assert!(node.save(&cli).is_ok());
+
+        node.get_properties_mut().unwrap().level = 3;
+        node.get_labels_mut().clear();
+        node.get_labels_mut().push("Admin".to_string());
+        assert!(node.is_dirty());
+        assert!(node.save(&cli).is_ok());
+        assert!(!node.is_dirty());
+
+        node.get_properties_mut().unwrap().name = "Jack".to_string();
+        assert!(node.reload(&cli).is_ok());
+        assert!(!node.is_dirty());
+        assert_eq!("Jane Doe", node.get_properties().as_ref().unwrap().name);
+
+        let requests = transport.requests();
+        assert_eq!(5, requests.len());
+        assert_eq!(Method::Put, requests[1].method);
+        assert_eq!("http://localhost:7474/db/data/node/17/properties/level", requests[1].uri);
+        assert_eq!(Some("3".to_string()), requests[1].body);
+        assert_eq!(Method::Post, requests[2].method);
+        assert_eq!(Some(r#"["Admin"]"#.to_string()), requests[2].body);
+        assert_eq!(Method::Delete, requests[3].method);
+        assert_eq!("http://localhost:7474/db/data/node/17/labels/Person", requests[3].uri);
+        assert_eq!(Method::Get, requests[4].method);
+    }
+
+    #[test]
+    pub fn test_node_find_by_label_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Ok, r#"[{"metadata":{"id":17,"labels":["Person"]},"data":{"name":"John Doe","level":-42}},{"metadata":{"id":18,"labels":["Person","Admin"]},"data":{"name":"Jane Doe","level":3}}]"#);
+        transport.push_response(StatusCode::Ok, r#"[{"metadata":{"id":18,"labels":["Person","Admin"]},"data":{"name":"Jane Doe","level":3}}]"#);
+        transport.push_response(StatusCode::Ok, "[]");
+        let cli = get_mock_client(&transport);
+
+        let nodes: Vec<node::Node<TestNodeData>> = node::Node::find_by_label(&cli, "Person").unwrap();
+        assert_eq!(vec![Some(17), Some(18)], nodes.iter().map(|node| node.get_id()).collect::<Vec<_>>());
+        assert_eq!("Jane Doe", nodes[1].get_properties().as_ref().unwrap().name);
+        assert_eq!(&vec!["Person".to_string(), "Admin".to_string()], nodes[1].get_labels());
+
+        let nodes: Vec<node::Node<TestNodeData>> = node::Node::find_by_label_and_property(&cli, "Person", "name", "Jane Doe").unwrap();
+        assert_eq!(1, nodes.len());
+        let nodes: Vec<node::Node> = node::Node::find_by_label_and_property(&cli, "Big Cat", "level", 3).unwrap();
+        assert!(nodes.is_empty());
+
+        let requests = transport.requests();
+        assert_eq!("http://localhost:7474/db/data/label/Person/nodes", requests[0].uri);
+        assert_eq!("http://localhost:7474/db/data/label/Person/nodes?name=%22Jane+Doe%22", requests[1].uri);
+        assert_eq!("http://localhost:7474/db/data/label/Big%20Cat/nodes?level=3", requests[2].uri);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    pub fn test_node_find_by_label_async_with_mock() {
+        use futures::executor::block_on;
+
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Ok, r#"[{"metadata":{"id":17,"labels":["Person"]},"data":{"name":"John Doe","level":-42}}]"#);
+        transport.push_response(StatusCode::NotFound, "");
+        let cli = get_mock_client(&transport);
+
+        let nodes: Vec<node::Node<TestNodeData>> = block_on(node::Node::find_by_label_async(&cli, "Person")).unwrap();
+        assert_eq!(Some(17), nodes[0].get_id());
+        let res: Result<Vec<node::Node<TestNodeData>>, _> = block_on(node::Node::find_by_label_and_property_async(&cli, "Person", "level", -42));
+        assert!(res.is_err());
+        assert_eq!("http://localhost:7474/db/data/label/Person/nodes?level=-42", transport.requests()[1].uri);
+    }
+
+    #[test]
+    pub fn test_node_labels_with_mock() {
+        let transport = MockTransport::new();
+        for _ in 0..4 {
+            transport.push_response(StatusCode::NoContent, "");
+        }
+        transport.push_response(StatusCode::Ok, r#"["Person","Admin"]"#);
+        let cli = get_mock_client(&transport);
+
+        let mut node: node::Node = node::Node::new();
+        node.id = Some(17);
+        assert!(node.add_labels(&cli, vec!["Person".to_string(), "say \"hi\"".to_string()]).is_ok());
+        assert_eq!(&vec!["Person".to_string(), "say \"hi\"".to_string()], node.get_labels());
+        assert!(node.add_labels(&cli, vec!["Person".to_string(), "User".to_string()]).is_ok());
+        assert_eq!(3, node.get_labels().len());
+        assert!(node.remove_label(&cli, "say \"hi\"".to_string()).is_ok());
+        assert_eq!(&vec!["Person".to_string(), "User".to_string()], node.get_labels());
+        assert!(node.set_labels(&cli, vec!["Admin".to_string()]).is_ok());
+        assert_eq!(&vec!["Admin".to_string()], node.get_labels());
+        assert!(node.fetch_labels(&cli).is_ok());
+        assert_eq!(&vec!["Person".to_string(), "Admin".to_string()], node.get_labels());
+
+        let requests = transport.requests();
+        assert_eq!(Method::Post, requests[0].method);
+        assert_eq!("http://localhost:7474/db/data/node/17/labels", requests[0].uri);
+        assert_eq!(Some(r#"["Person","say \"hi\""]"#.to_string()), requests[0].body);
+        assert_eq!(Method::Delete, requests[2].method);
+        assert_eq!("http://localhost:7474/db/data/node/17/labels/say%20%22hi%22", requests[2].uri);
+        assert_eq!(Method::Put, requests[3].method);
+        assert_eq!(Some(r#"["Admin"]"#.to_string()), requests[3].body);
+        assert_eq!(Method::Get, requests[4].method);
+    }
+
+    #[test]
+    pub fn test_node_labels_with_mock_kept_on_failure() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::NotFound, "");
+        let cli = get_mock_client(&transport);
+
+        let mut node: node::Node = node::Node::new();
+        assert!(node.set_labels(&cli, vec!["Person".to_string()]).is_err());
+        node.id = Some(17);
+        assert!(node.set_labels(&cli, vec!["Person".to_string()]).is_err());
+        assert!(node.get_labels().is_empty());
+        assert_eq!(1, transport.requests().len());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    pub fn test_node_labels_async_with_mock() {
+        use futures::executor::block_on;
+
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::NoContent, "");
+        transport.push_response(StatusCode::NoContent, "");
+        transport.push_response(StatusCode::Ok, r#"["Person"]"#);
+        let cli = get_mock_client(&transport);
+
+        let mut node: node::Node = node::Node::new();
+        node.id = Some(17);
+        assert!(block_on(node.set_labels_async(&cli, vec!["Person".to_string(), "User".to_string()])).is_ok());
+        assert!(block_on(node.remove_label_async(&cli, "User".to_string())).is_ok());
+        assert_eq!(&vec!["Person".to_string()], node.get_labels());
+        node.labels.clear();
+        assert!(block_on(node.fetch_labels_async(&cli)).is_ok());
+        assert_eq!(&vec!["Person".to_string()], node.get_labels());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    pub fn test_node_properties_async_with_mock() {
+        use futures::executor::block_on;
+
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::NoContent, "");
+        transport.push_response(StatusCode::NoContent, "");
+        transport.push_response(StatusCode::Ok, "42");
+        transport.push_response(StatusCode::NoContent, "");
+        transport.push_response(StatusCode::NoContent, "");
+        let cli = get_mock_client(&transport);
+
+        let mut node: node::Node<TestNodeData> = node::Node::new();
+        node.id = Some(17);
+        node.set_properties(TestNodeData { name: "John Doe".to_string(), level: 3 });
+        assert!(block_on(node.update_properties_async(&cli)).is_ok());
+        assert!(!node.is_dirty());
+        assert!(block_on(node.set_property_async(&cli, "level".to_string(), 42)).is_ok());
+        assert_eq!(42, node.get_properties().as_ref().unwrap().level);
+        assert!(!node.is_dirty());
+        assert_eq!(42, block_on(node.get_property_async::<i64>(&cli, "level".to_string())).unwrap());
+        assert!(block_on(node.remove_property_async(&cli, "nickname".to_string())).is_ok());
+        assert!(!node.is_dirty());
+        assert!(block_on(node.clear_properties_async(&cli)).is_ok());
+        assert!(!node.is_dirty());
+
+        let requests = transport.requests();
+        assert_eq!(Some(r#"{"name":"John Doe","level":3}"#.to_string()), requests[0].body);
+        assert_eq!("http://localhost:7474/db/data/node/17/properties/level", requests[1].uri);
+        assert_eq!("http://localhost:7474/db/data/node/17/properties/nickname", requests[3].uri);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    pub fn test_node_async_with_mock() {
+        use futures::executor::block_on;
+
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Created, r#"{"metadata":{"id":17,"labels":[]},"data":{"name":"John Doe","level":-42}}"#);
+        transport.push_response(StatusCode::NoContent, "");
+        transport.push_response(StatusCode::Ok, r#"{"metadata":{"id":17,"labels":["foo"]},"data":{"name":"John Doe","level":-42}}"#);
+        transport.push_response(StatusCode::NoContent, "");
+        let cli = get_mock_client(&transport);
+
+        let mut node: node::Node<TestNodeData> = node::Node::new();
+        node.set_properties(TestNodeData { name: "John Doe".to_string(), level: -42 });
+        assert!(block_on(node.add_async(&cli)).is_ok());
+        assert_eq!(Some(17), node.get_id());
+        assert!(block_on(node.add_labels_async(&cli, vec!["foo".to_string()])).is_ok());
+
+        let node_reload: node::Node<TestNodeData> = block_on(node::Node::get_async(&cli, 17)).unwrap();
+        assert_eq!(node_reload.labels, vec!["foo".to_string()]);
+        assert!(block_on(node.delete_async(&cli)).is_ok());
+
+        let requests = transport.requests();
+        assert_eq!(4, requests.len());
+        assert_eq!("http://localhost:7474/db/data/node/17/labels", requests[1].uri);
+        assert_eq!(Method::Delete, requests[3].method);
+    }
+}
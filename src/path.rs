@@ -1,9 +1,13 @@
 use rustc_serialize::{json, Decodable};
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::sync::Arc;
 use std::io::Read;
 pub use types::Error;
 use hyper;
+use client::RequestBuilder;
+use transport::Response;
+
+#[cfg(feature = "async")] use futures::future::{self, BoxFuture, FutureExt};
 
 #[derive(Debug)]
 pub enum Algorithm {
@@ -18,9 +22,12 @@ pub enum RelationshipType {
     To,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum RelationshipDirection {
     In,
     Out,
+    // Both incoming and outgoing.
+    All,
 }
 
 enum ResultNumericity {
@@ -39,7 +46,7 @@ pub struct Path {
     relationships: Vec<String>,
 }
 
-#[derive(RustcEncodable)]
+#[derive(RustcEncodable, Clone)]
 struct PathBuilderParam {
     to: String,
     cost_property: Option<String>,
@@ -64,20 +71,20 @@ impl PathBuilderParam {
 
 pub struct PathBuilder {
     from: u64,
+    to: u64,
     param: PathBuilderParam,
-    cli: Rc<::client::Client>,
+    cli: Arc<::client::Client>,
 }
 
 impl PathBuilder {
-    pub fn new(cli: Rc<::client::Client>, from: u64, to: u64) -> PathBuilder {
-        let mut instance = PathBuilder {
+    pub fn new(cli: Arc<::client::Client>, from: u64, to: u64) -> PathBuilder {
+        // The target is sent as a node URL, resolved with the service root when the request is made.
+        PathBuilder {
             param: PathBuilderParam::new(),
             cli: cli,
             from: from,
-        };
-
-        instance.param.to = instance.cli.as_ref().build_uri(format!("/db/data/node/{}", to));
-        instance
+            to: to,
+        }
     }
 
     pub fn path_with_depth(mut self, algorithm: Algorithm, max_depth: usize) -> PathBuilder {
@@ -111,26 +118,65 @@ impl PathBuilder {
         self.get(ResultNumericity::One)
     }
 
+    #[cfg(feature = "async")]
+    pub fn get_all_async(&self) -> BoxFuture<'static, Result<Vec<Path>, Error>> {
+        self.get_async(ResultNumericity::Multiple)
+    }
+
+    #[cfg(feature = "async")]
+    pub fn get_one_async(&self) -> BoxFuture<'static, Result<Path, Error>> {
+        self.get_async(ResultNumericity::One)
+    }
+
     fn get<T: Decodable>(&self, result_numericity: ResultNumericity) -> Result<T, Error> {
+        let req = match self.request(result_numericity) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        let res = try_rest!(req);
+        from_response(res)
+    }
+
+    #[cfg(feature = "async")]
+    fn get_async<T: Decodable + Send + 'static>(&self, result_numericity: ResultNumericity) -> BoxFuture<'static, Result<T, Error>> {
+        let req = match self.request(result_numericity) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(req)
+            .map(|res| res.and_then(from_response))
+            .boxed()
+    }
+
+    fn request(&self, result_numericity: ResultNumericity) -> Result<RequestBuilder<'_>, Error> {
+        let root = match self.cli.service_root() {
+            Ok(root) => root,
+            Err(err) => return Err(err),
+        };
         let path = match result_numericity {
-            ResultNumericity::One => format!("/db/data/node/{}/path", self.from),
-            ResultNumericity::Multiple => format!("/db/data/node/{}/paths", self.from),
+            ResultNumericity::One => format!("{}/path", root.node_uri(self.from)),
+            ResultNumericity::Multiple => format!("{}/paths", root.node_uri(self.from)),
         };
-        let payload = match json::encode(&self.param) {
+        let mut param = self.param.clone();
+        param.to = self.cli.build_uri(root.node_uri(self.to));
+        let payload = match json::encode(&param) {
             Ok(s) => s,
-            _ => return Err(Error::DataError),
+            Err(err) => return Err(Error::from(err)),
         };
 
-        let mut res_war = String::new();
-        let mut res = try_rest!(self.cli.as_ref().post(path).body(&*payload));
-        let _ = res.read_to_string(&mut res_war);
-        Ok(match json::decode::<T>(&res_war) {
-            Ok(obj) => obj,
-            _ => return Err(Error::DataError),
-        })
+        Ok(self.cli.as_ref().post(self.cli.build_uri(path)).body(&*payload))
     }
 }
 
+fn from_response<T: Decodable>(mut res: Response) -> Result<T, Error> {
+    let mut res_war = String::new();
+    let _ = res.read_to_string(&mut res_war);
+    Ok(match json::decode::<T>(&res_war) {
+        Ok(obj) => obj,
+        Err(err) => return Err(Error::from(err)),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use std::env;
@@ -138,7 +184,9 @@ mod tests {
     use node;
     use path;
     use relationship;
-    use std::rc::Rc;
+    use std::sync::Arc;
+    use hyper::status::StatusCode;
+    use transport::MockTransport;
     pub use types::Error;
 
     #[derive(RustcEncodable, RustcDecodable)]
@@ -157,10 +205,19 @@ mod tests {
             .get()
     }
 
+    fn get_mock_client(transport: &MockTransport) -> ::client::Client {
+        let builder = client::ClientBuilder::new()
+            .discovery(false)
+            .transport(transport.clone());
+        #[cfg(feature = "async")]
+        let builder = builder.async_transport(transport.clone());
+        builder.get()
+    }
+
     // 1, 2 and 3 are connected, 4 is single:
     // 1 -> 2 -> 3 | 4
-    fn setup() -> (Rc<client::Client>, Vec<relationship::Relationship<TestWeightedType>>, Vec<node::Node>) {
-        let cli = Rc::new(get_client());
+    fn setup() -> (Arc<client::Client>, Vec<relationship::Relationship<TestWeightedType>>, Vec<node::Node>) {
+        let cli = Arc::new(get_client());
 
         let mut node_1: node::Node = node::Node::new();
         assert!(node_1.add(cli.as_ref()).is_ok());
@@ -288,4 +345,23 @@ mod tests {
             assert!(n.delete(cli.as_ref()).is_ok());
         }
     }
+
+    #[test]
+    fn test_get_weighted_path_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Ok, r#"{"directions":["->","->"],"weight":3.7,"start":"http://localhost:7474/db/data/node/1","end":"http://localhost:7474/db/data/node/3","nodes":["http://localhost:7474/db/data/node/1","http://localhost:7474/db/data/node/2","http://localhost:7474/db/data/node/3"],"length":2,"relationships":["http://localhost:7474/db/data/relationship/1","http://localhost:7474/db/data/relationship/2"]}"#);
+        let cli = Arc::new(get_mock_client(&transport));
+
+        let p = path::PathBuilder::new(cli, 1, 3)
+            .path_with_weight("weight".to_string(), 1.0)
+            .get_one()
+            .unwrap();
+        assert_eq!(3.7, p.weight.unwrap());
+        assert_eq!(2, p.length);
+
+        let requests = transport.requests();
+        assert_eq!("http://localhost:7474/db/data/node/1/path", requests[0].uri);
+        assert!(requests[0].body.as_ref().unwrap().contains(r#""algorithm":"dijkstra""#));
+        assert!(requests[0].body.as_ref().unwrap().contains(r#""to":"http://localhost:7474/db/data/node/3""#));
+    }
 }
\ No newline at end of file
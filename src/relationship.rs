@@ -2,8 +2,15 @@ use rustc_serialize::{json, Encodable, Decodable, Encoder};
 use std::collections::HashMap;
 use hyper;
 use std::io::Read;
+use client::RequestBuilder;
+use changes::{self, Changes, Snapshot};
+use path::RelationshipDirection;
+use url::percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
+use transport::Response;
 pub use types::Error;
 
+#[cfg(feature = "async")] use futures::future::{self, BoxFuture, FutureExt, TryFutureExt};
+
 #[derive(RustcDecodable, Debug)]
 struct RelationshipMetadataResult {
     id: u64,
@@ -41,41 +48,182 @@ pub struct Relationship<T = RelationshipUnidentifiedResult> {
     from: u64,
     to: u64,
     properties: Option<T>,
+    snapshot: Snapshot,
 }
 
 impl<T: Encodable + Decodable> Relationship<T> {
     pub fn get(cli: &::client::Client, id: u64) -> Result<Relationship<T>, Error> {
-        let path = format!("/db/data/relationship/{}", id);
-        let mut payload = String::new();
-        let mut res = try_rest!(cli.get(path), Ok);
+        let req = match Self::get_request(cli, id) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        let res = try_rest!(req, Ok);
+        Self::from_get_response(res)
+    }
+
+    fn get_request(cli: &::client::Client, id: u64) -> Result<RequestBuilder<'_>, Error> {
+        match cli.endpoint(|root| root.relationship_uri(id)) {
+            Ok(path) => Ok(cli.get(path)),
+            Err(err) => Err(err),
+        }
+    }
 
+    fn from_get_response(mut res: Response) -> Result<Relationship<T>, Error> {
+        let mut payload = String::new();
         let _ = res.read_to_string(&mut payload);
-        let rel_raw  = match json::Json::from_str(&payload) {
-            Ok(rel_raw) => rel_raw,
-            _ => return Err(Error::DataError),
-        };
+        match json::Json::from_str(&payload) {
+            Ok(rel_json) => Self::from_json(rel_json),
+            Err(err) => Err(Error::from(err)),
+        }
+    }
 
-        // TODO load typed json to get data
-        let rel_typed: RelationshipResult<T> = match json::decode(&payload) {
+    // A relationship as the server represents it, the node ids are taken from the start and end URLs.
+    fn from_json(rel_json: json::Json) -> Result<Relationship<T>, Error> {
+        let type_name = match rel_json.find("type").and_then(|type_name| type_name.as_string()) {
+            Some(type_name) => type_name.to_string(),
+            None => return Err(Error::DataError(From::from(format!("Relationship without type: {}", rel_json)))),
+        };
+        let rel_typed: RelationshipResult<T> = match Decodable::decode(&mut json::Decoder::new(rel_json)) {
             Ok(rel_typed) => rel_typed,
-            _ => return Err(Error::DataError),
+            Err(err) => return Err(Error::from(err)),
         };
 
-        // TODO extract to a method with the same one from *.all_for_node()
-        let obj = rel_raw.as_object().unwrap();
+        let from = match get_node_id_from_url(&rel_typed.start) {
+            Ok(from) => from,
+            Err(err) => return Err(err),
+        };
+        let to = match get_node_id_from_url(&rel_typed.end) {
+            Ok(to) => to,
+            Err(err) => return Err(err),
+        };
+
+        let properties = Some(rel_typed.data);
         Ok(Relationship {
-            id: id,
-            type_name: obj.get("type").unwrap().as_string().unwrap().to_string(),
-            from: get_node_id_from_url(obj.get("start").unwrap().as_string().unwrap().to_string()),
-            to: get_node_id_from_url(obj.get("end").unwrap().as_string().unwrap().to_string()),
-            properties: Some(rel_typed.data),
+            id: rel_typed.metadata.id,
+            type_name: type_name,
+            from: from,
+            to: to,
+            snapshot: Snapshot::take(&properties, &[]).unwrap_or_default(),
+            properties: properties,
         })
     }
 
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    // Id of the start node.
+    pub fn from(&self) -> u64 {
+        self.from
+    }
+
+    // Id of the end node.
+    pub fn to(&self) -> u64 {
+        self.to
+    }
+
+    pub fn properties(&self) -> Option<&T> {
+        self.properties.as_ref()
+    }
+
+    // Re-fetches the properties, the local changes are lost.
+    pub fn reload(&mut self, cli: &::client::Client) -> Result<(), Error> {
+        let req = match Self::get_request(cli, self.id) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        let res = try_rest!(req, Ok);
+        match Self::from_get_response(res) {
+            Ok(rel) => {
+                *self = rel;
+                Ok(())
+            },
+            Err(err) => Err(err),
+        }
+    }
+
+    // Local changes are pushed with save(), see changes().
+    pub fn set_properties(&mut self, properties: T) {
+        self.properties = Some(properties);
+    }
+
+    pub fn properties_mut(&mut self) -> Option<&mut T> {
+        self.properties.as_mut()
+    }
+
+    // Properties changed locally since the relationship was last fetched, reloaded or saved.
+    pub fn changes(&self) -> Result<Changes, Error> {
+        self.snapshot.diff(&self.properties, &[])
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        match self.changes() {
+            Ok(changes) => !changes.is_empty(),
+            Err(_) => true,
+        }
+    }
+
+    // Pushes only the changed and removed properties, one request for each, see `node::Node::save()`.
+    pub fn save(&mut self, cli: &::client::Client) -> Result<(), Error> {
+        let reqs = match self.save_requests(cli) {
+            Ok(reqs) => reqs,
+            Err(err) => return Err(err),
+        };
+        for (req, change) in reqs {
+            try_rest!(req, NoContent);
+            self.snapshot.apply(change);
+        }
+
+        info!("Relationship saved: {}", self.id);
+        Ok(())
+    }
+
+    fn save_requests<'c>(&self, cli: &'c ::client::Client) -> Result<Vec<(RequestBuilder<'c>, Changes)>, Error> {
+        let changes = match self.changes() {
+            Ok(changes) => changes,
+            Err(err) => return Err(err),
+        };
+
+        let mut reqs = Vec::new();
+        for (key, value) in changes.set_properties {
+            let req = match self.set_property_request(cli, key.clone(), &value) {
+                Ok(req) => req,
+                Err(err) => return Err(err),
+            };
+            let mut change = Changes::default();
+            change.set_properties.insert(key, value);
+            reqs.push((req, change));
+        }
+        for key in changes.removed_properties {
+            let path = match self.properties_path(cli, Some(&key)) {
+                Ok(path) => path,
+                Err(err) => return Err(err),
+            };
+            reqs.push((cli.delete(path), Changes { removed_properties: vec![key], ..Changes::default() }));
+        }
+        Ok(reqs)
+    }
+
     pub fn connect(cli: &::client::Client, id_from: u64, id_to: u64, type_name: String, properties: Option<T>) -> Result<Relationship<T>, Error> {
+        let req = match Self::connect_request(cli, id_from, id_to, &type_name, properties) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        let res = try_rest!(req, Created);
+        Self::from_connect_response(id_from, id_to, type_name, res)
+    }
+
+    fn connect_request<'c>(cli: &'c ::client::Client, id_from: u64, id_to: u64, type_name: &String, properties: Option<T>) -> Result<RequestBuilder<'c>, Error> {
+        let root = match cli.service_root() {
+            Ok(root) => root,
+            Err(err) => return Err(err),
+        };
         let mut rel_data:HashMap<String, RelationshipDataField<T>> = HashMap::new();
-        let path: String = format!("/db/data/node/{}", id_to);
-        rel_data.insert("to".to_string(), RelationshipDataField::Text(cli.build_uri(path)));
+        rel_data.insert("to".to_string(), RelationshipDataField::Text(cli.build_uri(root.node_uri(id_to))));
         rel_data.insert("type".to_string(), RelationshipDataField::Text(type_name.clone()));
 
         if properties.is_some() {
@@ -84,71 +232,388 @@ impl<T: Encodable + Decodable> Relationship<T> {
 
         let rel_data_string = json::encode(&rel_data).unwrap();
 
-        let path:String = format!("/db/data/node/{}/relationships", id_from);
-        let mut res = try_rest!(cli.post(path).body(&rel_data_string), Created);
+        let path = cli.build_uri(format!("{}/relationships", root.node_uri(id_from)));
+        Ok(cli.post(path).body(&rel_data_string))
+    }
 
+    fn from_connect_response(id_from: u64, id_to: u64, type_name: String, mut res: Response) -> Result<Relationship<T>, Error> {
         let mut res_raw = String::new();
         let _ = res.read_to_string(&mut res_raw);
         let rel_json:RelationshipResult<T> = match json::decode(&res_raw) {
             Ok(j) => j,
-            _ => return Err(Error::DataError),
+            Err(err) => return Err(Error::from(err)),
         };
+        let properties = Some(rel_json.data);
         let rel = Relationship {
             id: rel_json.metadata.id,
-            type_name: type_name.clone(),
+            type_name: type_name,
             from: id_from,
             to: id_to,
-            properties: Some(rel_json.data),
+            snapshot: Snapshot::take(&properties, &[]).unwrap_or_default(),
+            properties: properties,
         };
 
         info!("Relationship has been created");
         Ok(rel)
     }
 
-    pub fn set_property<PropT: Encodable>(&self, cli: &::client::Client, prop: String, val: PropT) -> Result<(), Error> {
+    pub fn set_property<PropT: Encodable>(&mut self, cli: &::client::Client, prop: String, val: PropT) -> Result<(), Error> {
+        let val = match changes::encode_value(&val) {
+            Ok(val) => val,
+            Err(err) => return Err(err),
+        };
+        let req = match self.set_property_request(cli, prop.clone(), &val) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        try_rest!(req, NoContent);
+        self.update_property(&prop, Some(val));
+        Ok(())
+    }
+
+    fn set_property_request<'c, PropT: Encodable>(&self, cli: &'c ::client::Client, prop: String, val: PropT) -> Result<RequestBuilder<'c>, Error> {
         let val = match json::encode(&val) {
             Ok(s) => s,
-            _ => return Err(Error::DataError),
+            Err(err) => return Err(Error::from(err)),
+        };
+        match self.properties_path(cli, Some(&prop)) {
+            Ok(path) => Ok(cli.put(path).body(&*val)),
+            Err(err) => Err(err),
+        }
+    }
+
+    // The properties on the server, the local ones are left as they are (see `reload()`).
+    pub fn get_properties(&self, cli: &::client::Client) -> Result<T, Error> {
+        let path = match self.properties_path(cli, None) {
+            Ok(path) => path,
+            Err(err) => return Err(err),
         };
-        let path = format!("/db/data/relationship/{}/properties/{}", self.id, prop);
-        try_rest!(cli.put(path).body(&*val), NoContent);
+        let res = try_rest!(cli.get(path), Ok);
+        read_json(res)
+    }
+
+    // The calls changing properties below keep the local properties (and the snapshot of save()) in sync after each
+    // successful call.
+
+    // Replaces all properties on the server.
+    pub fn replace_properties(&mut self, cli: &::client::Client, properties: T) -> Result<(), Error> {
+        let req = match self.replace_properties_request(cli, &properties) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        try_rest!(req, NoContent);
+        self.set_local_properties(properties);
         Ok(())
     }
 
-    pub fn delete(&self, cli: &::client::Client) -> Result<(), Error> {
-        let path:String = format!("/db/data/relationship/{}", self.id);
+    fn replace_properties_request<'c>(&self, cli: &'c ::client::Client, properties: &T) -> Result<RequestBuilder<'c>, Error> {
+        let properties = match json::encode(properties) {
+            Ok(s) => s,
+            Err(err) => return Err(Error::from(err)),
+        };
+        match self.properties_path(cli, None) {
+            Ok(path) => Ok(cli.put(path).body(&properties)),
+            Err(err) => Err(err),
+        }
+    }
+
+    // A missing property is a NotFound response error.
+    pub fn get_property<V: Decodable>(&self, cli: &::client::Client, key: String) -> Result<V, Error> {
+        let path = match self.properties_path(cli, Some(&key)) {
+            Ok(path) => path,
+            Err(err) => return Err(err),
+        };
+        let res = try_rest!(cli.get(path), Ok);
+        read_json(res)
+    }
+
+    pub fn remove_property(&mut self, cli: &::client::Client, key: String) -> Result<(), Error> {
+        let path = match self.properties_path(cli, Some(&key)) {
+            Ok(path) => path,
+            Err(err) => return Err(err),
+        };
         try_rest!(cli.delete(path), NoContent);
+        self.update_property(&key, None);
+        Ok(())
+    }
+
+    pub fn clear_properties(&mut self, cli: &::client::Client) -> Result<(), Error> {
+        let path = match self.properties_path(cli, None) {
+            Ok(path) => path,
+            Err(err) => return Err(err),
+        };
+        try_rest!(cli.delete(path), NoContent);
+        self.snapshot.clear_properties();
+        self.properties = None;
+        Ok(())
+    }
+
+    fn properties_path(&self, cli: &::client::Client, key: Option<&str>) -> Result<String, Error> {
+        let id = self.id;
+        cli.endpoint(|root| match key {
+            Some(key) => format!("{}/properties/{}", root.relationship_uri(id), utf8_percent_encode(key, PATH_SEGMENT_ENCODE_SET)),
+            None => format!("{}/properties", root.relationship_uri(id)),
+        })
+    }
+
+    fn set_local_properties(&mut self, properties: T) {
+        self.properties = Some(properties);
+        if let Err(err) = self.snapshot.set_properties(&self.properties) {
+            warn!("Snapshot of relationship {} not updated: {}", self.id, err);
+        }
+    }
+
+    fn update_property(&mut self, key: &str, value: Option<json::Json>) {
+        if let Err(err) = changes::update_property(&mut self.properties, &mut self.snapshot, key, value) {
+            warn!("Local properties of relationship {} not updated: {}", self.id, err);
+        }
+    }
+
+    pub fn delete(&self, cli: &::client::Client) -> Result<(), Error> {
+        let req = match self.delete_request(cli) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        try_rest!(req, NoContent);
         info!("Relationship deleted: {}", self.id);
         Ok(())
     }
+
+    fn delete_request<'c>(&self, cli: &'c ::client::Client) -> Result<RequestBuilder<'c>, Error> {
+        let id = self.id;
+        match cli.endpoint(|root| root.relationship_uri(id)) {
+            Ok(path) => Ok(cli.delete(path)),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+// Non-blocking variants, they share the request building and response handling with the blocking calls above.
+#[cfg(feature = "async")]
+impl<T: Encodable + Decodable + Send + 'static> Relationship<T> {
+    pub fn get_async(cli: &::client::Client, id: u64) -> BoxFuture<'static, Result<Relationship<T>, Error>> {
+        let req = match Self::get_request(cli, id) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(req, Ok)
+            .map(|res| res.and_then(Self::from_get_response))
+            .boxed()
+    }
+
+    pub fn connect_async(cli: &::client::Client, id_from: u64, id_to: u64, type_name: String, properties: Option<T>) -> BoxFuture<'static, Result<Relationship<T>, Error>> {
+        let req = match Self::connect_request(cli, id_from, id_to, &type_name, properties) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(req, Created)
+            .map(move |res| res.and_then(|res| Self::from_connect_response(id_from, id_to, type_name, res)))
+            .boxed()
+    }
+
+    pub fn reload_async<'a>(&'a mut self, cli: &::client::Client) -> BoxFuture<'a, Result<(), Error>> {
+        let req = match Self::get_request(cli, self.id) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(req, Ok)
+            .map(move |res| res.and_then(Self::from_get_response).map(|rel| *self = rel))
+            .boxed()
+    }
+
+    // The requests are sent concurrently, see `node::Node::save_async()`.
+    pub fn save_async<'a>(&'a mut self, cli: &::client::Client) -> BoxFuture<'a, Result<(), Error>> {
+        let reqs = match self.save_requests(cli) {
+            Ok(reqs) => reqs,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        let (futures, changes): (Vec<_>, Vec<_>) = reqs.into_iter()
+            .map(|(req, change)| (try_rest_async!(req, NoContent), change))
+            .unzip();
+        future::try_join_all(futures)
+            .map_ok(move |_| {
+                for change in changes {
+                    self.snapshot.apply(change);
+                }
+                info!("Relationship saved: {}", self.id);
+            })
+            .boxed()
+    }
+
+    pub fn set_property_async<'a, PropT: Encodable>(&'a mut self, cli: &::client::Client, prop: String, val: PropT) -> BoxFuture<'a, Result<(), Error>> {
+        let val = match changes::encode_value(&val) {
+            Ok(val) => val,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        let req = match self.set_property_request(cli, prop.clone(), &val) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(req, NoContent)
+            .map_ok(move |_| self.update_property(&prop, Some(val)))
+            .boxed()
+    }
+
+    pub fn get_properties_async(&self, cli: &::client::Client) -> BoxFuture<'static, Result<T, Error>> {
+        let path = match self.properties_path(cli, None) {
+            Ok(path) => path,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(cli.get(path), Ok)
+            .map(|res| res.and_then(read_json))
+            .boxed()
+    }
+
+    pub fn replace_properties_async<'a>(&'a mut self, cli: &::client::Client, properties: T) -> BoxFuture<'a, Result<(), Error>> {
+        let req = match self.replace_properties_request(cli, &properties) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(req, NoContent)
+            .map_ok(move |_| self.set_local_properties(properties))
+            .boxed()
+    }
+
+    pub fn get_property_async<V: Decodable + Send + 'static>(&self, cli: &::client::Client, key: String) -> BoxFuture<'static, Result<V, Error>> {
+        let path = match self.properties_path(cli, Some(&key)) {
+            Ok(path) => path,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(cli.get(path), Ok)
+            .map(|res| res.and_then(read_json))
+            .boxed()
+    }
+
+    pub fn remove_property_async<'a>(&'a mut self, cli: &::client::Client, key: String) -> BoxFuture<'a, Result<(), Error>> {
+        let path = match self.properties_path(cli, Some(&key)) {
+            Ok(path) => path,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(cli.delete(path), NoContent)
+            .map_ok(move |_| self.update_property(&key, None))
+            .boxed()
+    }
+
+    pub fn clear_properties_async<'a>(&'a mut self, cli: &::client::Client) -> BoxFuture<'a, Result<(), Error>> {
+        let path = match self.properties_path(cli, None) {
+            Ok(path) => path,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(cli.delete(path), NoContent)
+            .map_ok(move |_| {
+                self.snapshot.clear_properties();
+                self.properties = None;
+            })
+            .boxed()
+    }
+
+    pub fn delete_async(&self, cli: &::client::Client) -> BoxFuture<'static, Result<(), Error>> {
+        let req = match self.delete_request(cli) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        let id = self.id;
+        try_rest_async!(req, NoContent)
+            .map_ok(move |_| info!("Relationship deleted: {}", id))
+            .boxed()
+    }
 }
 
 pub struct RelationshipCollection;
 
+// Relationships of a node filtered by direction and types on the server, eg.
+// `RelationshipCollection::for_node(&cli, 17).direction(RelationshipDirection::Out).types(vec!["KNOWS".to_string()]).get()`.
+pub struct RelationshipQuery<'c> {
+    cli: &'c ::client::Client,
+    id: u64,
+    direction: RelationshipDirection,
+    types: Vec<String>,
+}
+
+impl<'c> RelationshipQuery<'c> {
+    pub fn direction(mut self, direction: RelationshipDirection) -> RelationshipQuery<'c> {
+        self.direction = direction;
+        self
+    }
+
+    // Relationships of any of the types, all types when empty.
+    pub fn types(mut self, types: Vec<String>) -> RelationshipQuery<'c> {
+        self.types = types;
+        self
+    }
+
+    // The properties are decoded into T, `RelationshipUnidentifiedResult` skips them.
+    pub fn get<T: Encodable + Decodable>(&self) -> Result<Vec<Relationship<T>>, Error> {
+        let req = match self.request() {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        let res = try_rest!(req);
+        RelationshipCollection::from_all_for_node_response(res)
+    }
+
+    #[cfg(feature = "async")]
+    pub fn get_async<T: Encodable + Decodable + Send + 'static>(&self) -> BoxFuture<'static, Result<Vec<Relationship<T>>, Error>> {
+        let req = match self.request() {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        try_rest_async!(req)
+            .map(|res| res.and_then(RelationshipCollection::from_all_for_node_response))
+            .boxed()
+    }
+
+    // .../node/{id}/relationships/{all|in|out}/{type1&type2}
+    fn request(&self) -> Result<RequestBuilder<'c>, Error> {
+        let direction = match self.direction {
+            RelationshipDirection::In => "in",
+            RelationshipDirection::Out => "out",
+            RelationshipDirection::All => "all",
+        };
+        let types = self.types.iter()
+            .map(|type_name| utf8_percent_encode(type_name, PATH_SEGMENT_ENCODE_SET).to_string())
+            .collect::<Vec<String>>()
+            .join("&");
+        let id = self.id;
+        match self.cli.endpoint(|root| if types.is_empty() {
+            format!("{}/relationships/{}", root.node_uri(id), direction)
+        } else {
+            format!("{}/relationships/{}/{}", root.node_uri(id), direction, types)
+        }) {
+            Ok(path) => Ok(self.cli.get(path)),
+            Err(err) => Err(err),
+        }
+    }
+}
+
 impl RelationshipCollection {
-    pub fn all_for_node(cli: &::client::Client, id: u64) -> Result<Vec<Relationship>, Error> {
-        let path = format!("/db/data/node/{}/relationships/all", id);
-        let mut res = try_rest!(cli.get(path));
+    pub fn all_for_node<T: Encodable + Decodable>(cli: &::client::Client, id: u64) -> Result<Vec<Relationship<T>>, Error> {
+        Self::for_node(cli, id).get()
+    }
+
+    #[cfg(feature = "async")]
+    pub fn all_for_node_async<T: Encodable + Decodable + Send + 'static>(cli: &::client::Client, id: u64) -> BoxFuture<'static, Result<Vec<Relationship<T>>, Error>> {
+        Self::for_node(cli, id).get_async()
+    }
+
+    // All relationships of the node unless filtered, see `RelationshipQuery`.
+    pub fn for_node(cli: &::client::Client, id: u64) -> RelationshipQuery<'_> {
+        RelationshipQuery {
+            cli: cli,
+            id: id,
+            direction: RelationshipDirection::All,
+            types: Vec::new(),
+        }
+    }
 
+    fn from_all_for_node_response<T: Encodable + Decodable>(mut res: Response) -> Result<Vec<Relationship<T>>, Error> {
         let mut res_raw = String::new();
         let _ = res.read_to_string(&mut res_raw);
-        let rels_result_object = match json::Json::from_str(&res_raw) {
-            Ok(rels) => rels,
-            _ => return Err(Error::DataError),
-        };
-        let rels_result = rels_result_object.as_array().unwrap();
-
-        let rels: Vec<Relationship> = rels_result.iter().map(|elem| {
-            let obj = elem.as_object().unwrap();
-            Relationship {
-                id: obj.get("metadata").unwrap().as_object().unwrap().get("id").unwrap().as_u64().unwrap(),
-                type_name: obj.get("type").unwrap().as_string().unwrap().to_string(),
-                from: get_node_id_from_url(obj.get("start").unwrap().as_string().unwrap().to_string()),
-                to: get_node_id_from_url(obj.get("end").unwrap().as_string().unwrap().to_string()),
-                properties: None,
-            }
-        }).collect();
-        Ok(rels)
+        match json::Json::from_str(&res_raw) {
+            Ok(json::Json::Array(rels)) => rels.into_iter().map(Relationship::from_json).collect(),
+            Ok(_) => Err(Error::DataError(From::from(format!("Unexpected relationships response: {}", res_raw)))),
+            Err(err) => Err(Error::from(err)),
+        }
     }
 }
 
@@ -156,8 +621,20 @@ impl RelationshipCollection {
  * Helper functions.
  */
 
-fn get_node_id_from_url(url: String) -> u64 {
-    url.split("node/").collect::<Vec<&str>>().last().unwrap().parse::<u64>().unwrap()
+fn get_node_id_from_url(url: &str) -> Result<u64, Error> {
+    match url.rsplit("node/").next().and_then(|id| id.parse::<u64>().ok()) {
+        Some(id) => Ok(id),
+        None => Err(Error::DataError(From::from(format!("Invalid node URL: {}", url)))),
+    }
+}
+
+fn read_json<V: Decodable>(mut res: Response) -> Result<V, Error> {
+    let mut res_raw = String::new();
+    let _ = res.read_to_string(&mut res_raw);
+    match json::decode(&res_raw) {
+        Ok(value) => Ok(value),
+        Err(err) => Err(Error::from(err)),
+    }
 }
 
 /******************************************************************************
@@ -170,6 +647,10 @@ mod tests {
     use client;
     use relationship;
     use node;
+    use path::RelationshipDirection;
+    use hyper::method::Method;
+    use hyper::status::StatusCode;
+    use transport::MockTransport;
     pub use types::Error;
 
     #[derive(RustcEncodable, RustcDecodable)]
@@ -189,6 +670,15 @@ mod tests {
             .get()
     }
 
+    fn get_mock_client(transport: &MockTransport) -> ::client::Client {
+        let builder = client::ClientBuilder::new()
+            .discovery(false)
+            .transport(transport.clone());
+        #[cfg(feature = "async")]
+        let builder = builder.async_transport(transport.clone());
+        builder.get()
+    }
+
     #[test]
     pub fn test_connect_nodes_no_type() {
         let cli = get_client();
@@ -250,7 +740,7 @@ mod tests {
         let res: Result<relationship::Relationship<TestRelationshipData>, Error> = relationship::Relationship::connect(&cli, node_parent.get_id().unwrap(), node_child.get_id().unwrap(), "Likes".to_string(), Some(TestRelationshipData { name: "Steve".to_string(), level: -6, }));
         assert!(res.is_ok());
 
-        let rel = res.unwrap();
+        let mut rel = res.unwrap();
 
         assert!(rel.set_property(&cli, "name".to_string(), "Walter".to_string()).is_ok());
 
@@ -279,11 +769,140 @@ mod tests {
 
         let rel = res.unwrap();
 
-        let rels = relationship::RelationshipCollection::all_for_node(&cli, node_parent.get_id().unwrap()).unwrap();
+        let rels: Vec<relationship::Relationship> = relationship::RelationshipCollection::all_for_node(&cli, node_parent.get_id().unwrap()).unwrap();
         assert_eq!(1, rels.len());
 
         assert!(rel.delete(&cli).is_ok());
         assert!(node_parent.delete(&cli).is_ok());
         assert!(node_child.delete(&cli).is_ok());
     }
+
+    #[test]
+    pub fn test_connect_and_load_all_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Created, r#"{"start":"http://localhost:7474/db/data/node/1","end":"http://localhost:7474/db/data/node/2","type":"Likes","metadata":{"id":5,"type":"Likes"},"data":{"name":"Steve","level":-6}}"#);
+        transport.push_response(StatusCode::Ok, r#"[{"start":"http://localhost:7474/db/data/node/1","end":"http://localhost:7474/db/data/node/2","type":"Likes","metadata":{"id":5,"type":"Likes"},"data":{"name":"Steve","level":-6}}]"#);
+        let cli = get_mock_client(&transport);
+
+        let rel: relationship::Relationship<TestRelationshipData> = relationship::Relationship::connect(&cli, 1, 2, "Likes".to_string(), Some(TestRelationshipData { name: "Steve".to_string(), level: -6, })).unwrap();
+        assert_eq!(5, rel.id);
+        assert_eq!(rel.properties.as_ref().unwrap().name, "Steve");
+
+        let rels = relationship::RelationshipCollection::all_for_node::<TestRelationshipData>(&cli, 1).unwrap();
+        assert_eq!(1, rels.len());
+        assert_eq!(5, rels[0].id());
+        assert_eq!(1, rels[0].from());
+        assert_eq!(2, rels[0].to());
+        assert_eq!("Likes", rels[0].type_name());
+        assert_eq!("Steve", rels[0].properties().unwrap().name);
+        assert_eq!(-6, rels[0].properties().unwrap().level);
+        assert!(!rels[0].is_dirty());
+
+        let requests = transport.requests();
+        assert_eq!(Method::Post, requests[0].method);
+        assert_eq!("http://localhost:7474/db/data/node/1/relationships", requests[0].uri);
+        assert_eq!("http://localhost:7474/db/data/node/1/relationships/all", requests[1].uri);
+    }
+
+    #[test]
+    pub fn test_relationships_for_node_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Ok, r#"[{"start":"http://localhost:7474/db/data/node/1","end":"http://localhost:7474/db/data/node/2","type":"KNOWS","metadata":{"id":5,"type":"KNOWS"},"data":{}}]"#);
+        transport.push_response(StatusCode::Ok, "[]");
+        transport.push_response(StatusCode::NotFound, "");
+        transport.push_response(StatusCode::Ok, r#"[{"start":"http://localhost:7474/db/data/node/1","end":"http://localhost:7474/db/data/node/","type":"KNOWS","metadata":{"id":5,"type":"KNOWS"},"data":{}}]"#);
+        let cli = get_mock_client(&transport);
+
+        let rels: Vec<relationship::Relationship> = relationship::RelationshipCollection::for_node(&cli, 1)
+            .direction(RelationshipDirection::Out)
+            .types(vec!["KNOWS".to_string(), "LIKES A LOT".to_string()])
+            .get()
+            .unwrap();
+        assert_eq!(1, rels.len());
+        assert_eq!("KNOWS", rels[0].type_name);
+
+        let query = relationship::RelationshipCollection::for_node(&cli, 2).direction(RelationshipDirection::In);
+        assert!(query.get::<relationship::RelationshipUnidentifiedResult>().unwrap().is_empty());
+        assert!(query.get::<relationship::RelationshipUnidentifiedResult>().is_err());
+        match query.get::<relationship::RelationshipUnidentifiedResult>() {
+            Err(relationship::Error::DataError(_)) => {},
+            _ => panic!("Expected data error"),
+        }
+
+        let requests = transport.requests();
+        assert_eq!("http://localhost:7474/db/data/node/1/relationships/out/KNOWS&LIKES%20A%20LOT", requests[0].uri);
+        assert_eq!("http://localhost:7474/db/data/node/2/relationships/in", requests[1].uri);
+    }
+
+    #[test]
+    pub fn test_properties_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Ok, r#"{"start":"http://localhost:7474/db/data/node/1","end":"http://localhost:7474/db/data/node/2","type":"Likes","metadata":{"id":5,"type":"Likes"},"data":{"name":"Steve","level":-6}}"#);
+        transport.push_response(StatusCode::Ok, r#"{"name":"Walter","level":1}"#);
+        transport.push_response(StatusCode::NoContent, "");
+        transport.push_response(StatusCode::NoContent, "");
+        transport.push_response(StatusCode::Ok, "9");
+        transport.push_response(StatusCode::NoContent, "");
+        transport.push_response(StatusCode::NoContent, "");
+        let cli = get_mock_client(&transport);
+
+        let mut rel: relationship::Relationship<TestRelationshipData> = relationship::Relationship::get(&cli, 5).unwrap();
+        assert_eq!("Walter", rel.get_properties(&cli).unwrap().name);
+        assert_eq!("Steve", rel.properties().unwrap().name);
+        assert!(rel.set_property(&cli, "level".to_string(), 3).is_ok());
+        assert_eq!(3, rel.properties().unwrap().level);
+        assert!(!rel.is_dirty());
+        assert!(rel.replace_properties(&cli, TestRelationshipData { name: "Jane".to_string(), level: 2 }).is_ok());
+        assert_eq!("Jane", rel.properties().unwrap().name);
+        assert_eq!(9, rel.get_property::<i64>(&cli, "level".to_string()).unwrap());
+        assert_eq!(2, rel.properties().unwrap().level);
+        assert!(!rel.is_dirty());
+        // TestRelationshipData can't be without a name, the local one is kept.
+        assert!(rel.remove_property(&cli, "name".to_string()).is_ok());
+        assert_eq!("Jane", rel.properties().unwrap().name);
+        assert!(rel.clear_properties(&cli).is_ok());
+        assert!(rel.properties().is_none());
+        assert!(!rel.is_dirty());
+
+        let requests = transport.requests();
+        assert_eq!(0, transport.pending_responses());
+        assert_eq!(Method::Get, requests[1].method);
+        assert_eq!("http://localhost:7474/db/data/relationship/5/properties", requests[1].uri);
+        assert_eq!(Method::Put, requests[2].method);
+        assert_eq!("http://localhost:7474/db/data/relationship/5/properties/level", requests[2].uri);
+        assert_eq!(Some("3".to_string()), requests[2].body);
+        assert_eq!(Method::Put, requests[3].method);
+        assert_eq!(Some(r#"{"name":"Jane","level":2}"#.to_string()), requests[3].body);
+        assert_eq!("http://localhost:7474/db/data/relationship/5/properties/level", requests[4].uri);
+        assert_eq!(Method::Delete, requests[5].method);
+        assert_eq!("http://localhost:7474/db/data/relationship/5/properties/name", requests[5].uri);
+        assert_eq!(Method::Delete, requests[6].method);
+        assert_eq!("http://localhost:7474/db/data/relationship/5/properties", requests[6].uri);
+    }
+
+    #[test]
+    pub fn test_reload_and_save_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Ok, r#"{"start":"http://localhost:7474/db/data/node/1","end":"http://localhost:7474/db/data/node/2","type":"Likes","metadata":{"id":5,"type":"Likes"},"data":{"name":"Steve","level":-6}}"#);
+        transport.push_response(StatusCode::NoContent, "");
+        transport.push_response(StatusCode::Ok, r#"{"start":"http://localhost:7474/db/data/node/1","end":"http://localhost:7474/db/data/node/2","type":"Likes","metadata":{"id":5,"type":"Likes"},"data":{"name":"Walter","level":7}}"#);
+        let cli = get_mock_client(&transport);
+
+        let mut rel: relationship::Relationship<TestRelationshipData> = relationship::Relationship::get(&cli, 5).unwrap();
+        assert!(!rel.is_dirty());
+        rel.properties_mut().unwrap().level = 7;
+        assert!(rel.save(&cli).is_ok());
+        assert!(!rel.is_dirty());
+
+        assert!(rel.reload(&cli).is_ok());
+        assert_eq!("Walter", rel.properties.as_ref().unwrap().name);
+        assert!(!rel.is_dirty());
+
+        let requests = transport.requests();
+        assert_eq!(3, requests.len());
+        assert_eq!(Method::Put, requests[1].method);
+        assert_eq!("http://localhost:7474/db/data/relationship/5/properties/level", requests[1].uri);
+        assert_eq!(Some("7".to_string()), requests[1].body);
+        assert_eq!("http://localhost:7474/db/data/relationship/5", requests[2].uri);
+    }
 }
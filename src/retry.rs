@@ -0,0 +1,250 @@
+use std::cmp;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use rand::{thread_rng, Rng};
+pub use types::Error;
+
+#[cfg(feature = "async")] use futures::future::{self, BoxFuture, FutureExt};
+#[cfg(feature = "async")] use tokio;
+
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: f64,
+    retryable: Arc<dyn Fn(&Error) -> bool + Send + Sync>,
+}
+
+// Transient Neo4j errors (deadlocks, leader switches, ...) and failed connections are worth another try.
+// Other network errors are not: the server may have applied a write before the connection broke.
+pub fn is_retryable_by_default(err: &Error) -> bool {
+    err.is_connect_failure() || err.is_transient()
+}
+
+// Opt-in predicate also retrying broken connections and timeouts, only for idempotent queries.
+pub fn is_retryable_with_network_errors(err: &Error) -> bool {
+    match *err {
+        Error::NetworkError(_) => true,
+        _ => err.is_transient(),
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            jitter: 0.2,
+            retryable: Arc::new(is_retryable_by_default),
+        }
+    }
+
+    pub fn none() -> RetryPolicy {
+        RetryPolicy::new().max_attempts(1)
+    }
+
+    pub fn max_attempts(mut self, max_attempts: u32) -> RetryPolicy {
+        self.max_attempts = cmp::max(1, max_attempts);
+        self
+    }
+
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> RetryPolicy {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    pub fn max_backoff(mut self, max_backoff: Duration) -> RetryPolicy {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    // Fraction of the backoff randomly added or subtracted, 0.0 disables jitter.
+    pub fn jitter(mut self, jitter: f64) -> RetryPolicy {
+        self.jitter = jitter.max(0.0).min(1.0);
+        self
+    }
+
+    pub fn retryable<F: Fn(&Error) -> bool + Send + Sync + 'static>(mut self, retryable: F) -> RetryPolicy {
+        self.retryable = Arc::new(retryable);
+        self
+    }
+
+    pub fn get_max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    pub fn is_retryable(&self, err: &Error) -> bool {
+        (self.retryable)(err)
+    }
+
+    // Delay before the given retry (1 = first retry): exponential growth capped by the max backoff.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponent = cmp::min(retry.saturating_sub(1), 31) as i32;
+        let base = duration_to_secs(self.initial_backoff) * 2f64.powi(exponent);
+        let max = duration_to_secs(self.max_backoff);
+        let mut delay = base.min(max);
+
+        if self.jitter > 0.0 && delay > 0.0 {
+            let spread = delay * self.jitter;
+            delay = thread_rng().gen_range(delay - spread, delay + spread).min(max);
+        }
+
+        secs_to_duration(delay)
+    }
+
+    pub fn run<T, F>(&self, mut operation: F) -> Result<T, Error> where F: FnMut() -> Result<T, Error> {
+        let mut attempt = 1;
+        loop {
+            match operation() {
+                Err(ref err) if attempt < self.max_attempts && self.is_retryable(err) => {
+                    let delay = self.backoff(attempt);
+                    warn!("Attempt {} of {} failed, retrying in {:?}: {}", attempt, self.max_attempts, delay, err);
+                    thread::sleep(delay);
+                    attempt += 1;
+                },
+                res => return res,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl RetryPolicy {
+    // Same as `run()`, the backoff is awaited on the tokio timer instead of blocking the thread.
+    pub fn run_async<T, F>(&self, operation: F) -> BoxFuture<'static, Result<T, Error>>
+        where T: Send + 'static, F: FnMut() -> BoxFuture<'static, Result<T, Error>> + Send + 'static {
+        run_attempt_async(self.clone(), operation, 1)
+    }
+}
+
+#[cfg(feature = "async")]
+fn run_attempt_async<T, F>(policy: RetryPolicy, mut operation: F, attempt: u32) -> BoxFuture<'static, Result<T, Error>>
+    where T: Send + 'static, F: FnMut() -> BoxFuture<'static, Result<T, Error>> + Send + 'static {
+    operation().then(move |res| match res {
+        Err(ref err) if attempt < policy.max_attempts && policy.is_retryable(err) => {
+            let delay = policy.backoff(attempt);
+            warn!("Attempt {} of {} failed, retrying in {:?}: {}", attempt, policy.max_attempts, delay, err);
+            tokio::time::sleep(delay)
+                .then(move |_| run_attempt_async(policy, operation, attempt + 1))
+                .boxed()
+        },
+        res => future::ready(res).boxed(),
+    }).boxed()
+}
+
+fn duration_to_secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1_000_000_000.0
+}
+
+fn secs_to_duration(secs: f64) -> Duration {
+    Duration::new(secs.trunc() as u64, (secs.fract() * 1_000_000_000.0) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::time::Duration;
+    use hyper::status::StatusCode;
+    use retry::{self, RetryPolicy};
+    use types::{Error, ResponseError};
+
+    fn get_transient_error() -> Error {
+        Error::ResponseError(ResponseError::new(StatusCode::Ok, r#"{"errors":[{"code":"Neo.TransientError.Transaction.DeadlockDetected","message":"Deadlock"}]}"#.to_string()))
+    }
+
+    fn get_policy() -> RetryPolicy {
+        RetryPolicy::new().initial_backoff(Duration::from_millis(0))
+    }
+
+    #[test]
+    fn test_backoff_is_exponential_and_capped() {
+        let policy = RetryPolicy::new()
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(500))
+            .jitter(0.0);
+        assert_eq!(Duration::from_millis(100), policy.backoff(1));
+        assert_eq!(Duration::from_millis(200), policy.backoff(2));
+        assert_eq!(Duration::from_millis(400), policy.backoff(3));
+        assert_eq!(Duration::from_millis(500), policy.backoff(4));
+        assert_eq!(Duration::from_millis(500), policy.backoff(100));
+    }
+
+    #[test]
+    fn test_backoff_jitter_stays_in_range() {
+        let policy = RetryPolicy::new()
+            .initial_backoff(Duration::from_millis(100))
+            .jitter(0.5);
+        for _ in 0..100 {
+            let delay = policy.backoff(1);
+            assert!(delay >= Duration::from_millis(50));
+            assert!(delay <= Duration::from_millis(150));
+        }
+    }
+
+    #[test]
+    fn test_run_retries_transient_errors() {
+        let mut calls = 0;
+        let res = get_policy().max_attempts(3).run(|| {
+            calls += 1;
+            if calls < 3 { Err(get_transient_error()) } else { Ok(calls) }
+        });
+        assert_eq!(3, res.unwrap());
+
+        let mut calls = 0;
+        let res: Result<(), Error> = get_policy().max_attempts(2).run(|| {
+            calls += 1;
+            Err(Error::from_connect_failure(io::Error::new(io::ErrorKind::ConnectionRefused, "refused")))
+        });
+        assert!(res.is_err());
+        assert_eq!(2, calls);
+    }
+
+    #[test]
+    fn test_run_retries_other_network_errors_on_opt_in() {
+        let mut calls = 0;
+        let res: Result<(), Error> = get_policy().run(|| {
+            calls += 1;
+            Err(Error::from(io::Error::new(io::ErrorKind::ConnectionReset, "reset")))
+        });
+        assert!(res.is_err());
+        assert_eq!(1, calls);
+
+        let mut calls = 0;
+        let res: Result<(), Error> = get_policy().max_attempts(2).retryable(retry::is_retryable_with_network_errors).run(|| {
+            calls += 1;
+            Err(Error::from(io::Error::new(io::ErrorKind::ConnectionReset, "reset")))
+        });
+        assert!(res.is_err());
+        assert_eq!(2, calls);
+    }
+
+    #[test]
+    fn test_run_does_not_retry_other_errors() {
+        let mut calls = 0;
+        let res: Result<(), Error> = get_policy().run(|| {
+            calls += 1;
+            Err(Error::IntegrityError)
+        });
+        assert!(res.is_err());
+        assert_eq!(1, calls);
+
+        let mut calls = 0;
+        let res: Result<(), Error> = get_policy().retryable(|_| false).run(|| {
+            calls += 1;
+            Err(get_transient_error())
+        });
+        assert!(res.is_err());
+        assert_eq!(1, calls);
+
+        let mut calls = 0;
+        let res: Result<(), Error> = RetryPolicy::none().run(|| {
+            calls += 1;
+            Err(get_transient_error())
+        });
+        assert!(res.is_err());
+        assert_eq!(1, calls);
+    }
+}
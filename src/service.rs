@@ -0,0 +1,300 @@
+use std::fmt;
+use std::io::Read;
+use std::sync::Arc;
+use rustc_serialize::json;
+use url::Url;
+use url::percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
+use transport::Response;
+pub use types::Error;
+
+// Path of the service root document, relative to the client's URL.
+pub const DISCOVERY_PATH: &str = "/db/data/";
+
+// Entry points of the REST API as the server advertises them in its service root document (GET /db/data/).
+// Like the ones of `ServiceRoot::standard()`, they are relative to the client's URL.
+#[derive(RustcDecodable, RustcEncodable, Clone, Debug, PartialEq)]
+pub struct ServiceRoot {
+    pub node: String,
+    pub relationship: String,
+    pub node_index: String,
+    pub relationship_index: String,
+    pub relationship_types: String,
+    pub batch: String,
+    pub indexes: String,
+    pub constraints: String,
+    pub transaction: String,
+    pub node_labels: String,
+    pub neo4j_version: Option<String>,
+    pub neo4j_edition: Option<String>,
+}
+
+impl ServiceRoot {
+    // The layout Neo4j uses under /db/data, for clients that don't discover it.
+    pub fn standard() -> ServiceRoot {
+        ServiceRoot {
+            node: "/db/data/node".to_string(),
+            relationship: "/db/data/relationship".to_string(),
+            node_index: "/db/data/index/node".to_string(),
+            relationship_index: "/db/data/index/relationship".to_string(),
+            relationship_types: "/db/data/relationship/types".to_string(),
+            batch: "/db/data/batch".to_string(),
+            indexes: "/db/data/schema/index".to_string(),
+            constraints: "/db/data/schema/constraint".to_string(),
+            transaction: "/db/data/transaction".to_string(),
+            node_labels: "/db/data/labels".to_string(),
+            neo4j_version: None,
+            neo4j_edition: None,
+        }
+    }
+
+    pub fn from_response(mut res: Response) -> Result<ServiceRoot, Error> {
+        let mut res_raw = String::new();
+        let _ = res.read_to_string(&mut res_raw);
+        match json::decode::<ServiceRoot>(&res_raw) {
+            Ok(root) => Ok(root.rebase()),
+            Err(err) => Err(Error::from(err)),
+        }
+    }
+
+    // Advertised URLs are built from the server's own view of its host and path, which is wrong behind a reverse
+    // proxy serving it under another host or prefix. Only their path below the service root document is kept.
+    fn rebase(self) -> ServiceRoot {
+        ServiceRoot {
+            node: rebase_uri(self.node),
+            relationship: rebase_uri(self.relationship),
+            node_index: rebase_uri(self.node_index),
+            relationship_index: rebase_uri(self.relationship_index),
+            relationship_types: rebase_uri(self.relationship_types),
+            batch: rebase_uri(self.batch),
+            indexes: rebase_uri(self.indexes),
+            constraints: rebase_uri(self.constraints),
+            transaction: rebase_uri(self.transaction),
+            node_labels: rebase_uri(self.node_labels),
+            neo4j_version: self.neo4j_version,
+            neo4j_edition: self.neo4j_edition,
+        }
+    }
+
+    pub fn node_uri(&self, id: u64) -> String {
+        format!("{}/{}", self.node, id)
+    }
+
+    pub fn relationship_uri(&self, id: u64) -> String {
+        format!("{}/{}", self.relationship, id)
+    }
+
+    // Nodes with a label, it's next to the advertised list of labels: .../labels -> .../label/{label}/nodes.
+    pub fn label_nodes_uri(&self, label: &str) -> String {
+        let base = self.node_labels.trim_end_matches('/');
+        let base = if base.ends_with("/labels") {
+            &base[..base.len() - 1]
+        } else {
+            base
+        };
+        format!("{}/{}/nodes", base, utf8_percent_encode(label, PATH_SEGMENT_ENCODE_SET))
+    }
+
+    pub fn index_uri(&self, label: &str) -> String {
+        format!("{}/{}", self.indexes, label)
+    }
+
+    // Endpoint of an open transaction, or the one beginning a new transaction.
+    pub fn transaction_uri(&self, id: Option<u64>) -> String {
+        match id {
+            Some(id) => format!("{}/{}", self.transaction, id),
+            None => self.transaction.clone(),
+        }
+    }
+
+    // Commits the open transaction, or runs the statements in a new one committed right away.
+    pub fn commit_uri(&self, id: Option<u64>) -> String {
+        format!("{}/commit", self.transaction_uri(id))
+    }
+}
+
+// "http://10.0.0.5:7474/db/data/node" -> "/db/data/node", URLs outside of the document's path are kept as they are.
+fn rebase_uri(uri: String) -> String {
+    let path = match Url::parse(&uri) {
+        Ok(url) => url.path().to_string(),
+        Err(_) => return uri,
+    };
+    match path.find(DISCOVERY_PATH) {
+        Some(start) => path[start..].to_string(),
+        None => uri,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    pub fn new(major: u32, minor: u32, patch: u32) -> Version {
+        Version {
+            major: major,
+            minor: minor,
+            patch: patch,
+        }
+    }
+
+    // Accepts versions like "3.5.14", "4.0" or "3.0.0-M01", anything after the numbers is ignored.
+    pub fn parse(version: &str) -> Option<Version> {
+        let numbers: Vec<&str> = version.split(['-', '+']).next().unwrap_or("").split('.').collect();
+        if numbers.len() < 2 || numbers.len() > 3 {
+            return None;
+        }
+        let mut parsed = Vec::new();
+        for number in numbers {
+            match number.parse::<u32>() {
+                Ok(number) => parsed.push(number),
+                Err(_) => return None,
+            }
+        }
+        Some(Version::new(parsed[0], parsed[1], *parsed.get(2).unwrap_or(&0)))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+// Capabilities that changed between Neo4j versions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Feature {
+    // `{param}` placeholders, removed in 4.0.
+    BraceParameters,
+    // `$param` placeholders, since 3.0.
+    DollarParameters,
+    // `START n=node(...)`, removed in 3.2.
+    StartClause,
+}
+
+#[derive(Clone, Debug)]
+pub struct ServerInfo {
+    pub version: Option<Version>,
+    pub edition: Option<String>,
+    pub service_root: Arc<ServiceRoot>,
+}
+
+impl ServerInfo {
+    pub fn new(service_root: Arc<ServiceRoot>) -> ServerInfo {
+        ServerInfo {
+            version: service_root.neo4j_version.as_ref().and_then(|version| Version::parse(version)),
+            edition: service_root.neo4j_edition.clone(),
+            service_root: service_root,
+        }
+    }
+
+    // Without a known version (eg. discovery is disabled) a current server is assumed.
+    pub fn supports(&self, feature: Feature) -> bool {
+        let version = match self.version {
+            Some(version) => version,
+            None => return feature == Feature::DollarParameters,
+        };
+        match feature {
+            Feature::BraceParameters => version < Version::new(4, 0, 0),
+            Feature::DollarParameters => version >= Version::new(3, 0, 0),
+            Feature::StartClause => version < Version::new(3, 2, 0),
+        }
+    }
+
+    // Placeholder of a Cypher query parameter in the syntax the server understands, eg. "$id" or "{id}".
+    pub fn parameter(&self, name: &str) -> String {
+        if self.supports(Feature::DollarParameters) {
+            format!("${}", name)
+        } else {
+            format!("{{{}}}", name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use hyper::status::StatusCode;
+    use service::{Feature, ServerInfo, ServiceRoot, Version};
+    use transport::Response;
+
+    #[test]
+    fn test_service_root_from_response() {
+        let body = r#"{
+            "extensions": {},
+            "node": "http://proxy/neo4j/db/data/node",
+            "relationship": "http://proxy/neo4j/db/data/relationship",
+            "node_index": "http://proxy/neo4j/db/data/index/node",
+            "relationship_index": "http://proxy/neo4j/db/data/index/relationship",
+            "extensions_info": "http://proxy/neo4j/db/data/ext",
+            "relationship_types": "http://proxy/neo4j/db/data/relationship/types",
+            "batch": "http://proxy/neo4j/db/data/batch",
+            "cypher": "http://proxy/neo4j/db/data/cypher",
+            "indexes": "http://proxy/neo4j/db/data/schema/index",
+            "constraints": "http://proxy/neo4j/db/data/schema/constraint",
+            "transaction": "http://proxy/neo4j/db/data/transaction",
+            "node_labels": "http://proxy/neo4j/db/data/labels",
+            "neo4j_version": "3.5.14"
+        }"#;
+        let root = ServiceRoot::from_response(Response::new(StatusCode::Ok, body.to_string())).unwrap();
+
+        assert_eq!(Some("3.5.14".to_string()), root.neo4j_version);
+        assert_eq!("/db/data/node/17", root.node_uri(17));
+        assert_eq!("/db/data/relationship/3", root.relationship_uri(3));
+        assert_eq!("/db/data/schema/index/Person", root.index_uri("Person"));
+        assert_eq!("/db/data/label/Big%20Cat/nodes", root.label_nodes_uri("Big Cat"));
+        assert_eq!("/db/data/transaction", root.transaction_uri(None));
+        assert_eq!("/db/data/transaction/5/commit", root.commit_uri(Some(5)));
+
+        assert!(ServiceRoot::from_response(Response::new(StatusCode::Ok, "{}".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_standard_service_root() {
+        let root = ServiceRoot::standard();
+        assert_eq!("/db/data/node/17", root.node_uri(17));
+        assert_eq!("/db/data/transaction/commit", root.commit_uri(None));
+        assert_eq!("/db/data/transaction/5", root.transaction_uri(Some(5)));
+    }
+
+    #[test]
+    fn test_version_parse() {
+        assert_eq!(Some(Version::new(3, 5, 14)), Version::parse("3.5.14"));
+        assert_eq!(Some(Version::new(4, 0, 0)), Version::parse("4.0"));
+        assert_eq!(Some(Version::new(3, 0, 0)), Version::parse("3.0.0-M01"));
+        assert_eq!(None, Version::parse("3"));
+        assert_eq!(None, Version::parse("dev"));
+        assert!(Version::new(3, 10, 0) > Version::new(3, 9, 1));
+        assert_eq!("2.3.1", Version::new(2, 3, 1).to_string());
+    }
+
+    #[test]
+    fn test_server_info_features() {
+        let server_info = |version: Option<&str>| {
+            let mut root = ServiceRoot::standard();
+            root.neo4j_version = version.map(str::to_string);
+            ServerInfo::new(Arc::new(root))
+        };
+
+        let info = server_info(Some("2.3.1"));
+        assert!(info.supports(Feature::BraceParameters));
+        assert!(!info.supports(Feature::DollarParameters));
+        assert!(info.supports(Feature::StartClause));
+        assert_eq!("{id}", info.parameter("id"));
+
+        let info = server_info(Some("3.5.14"));
+        assert!(info.supports(Feature::BraceParameters));
+        assert!(!info.supports(Feature::StartClause));
+        assert_eq!("$id", info.parameter("id"));
+
+        let info = server_info(Some("4.4.0"));
+        assert!(!info.supports(Feature::BraceParameters));
+        assert!(info.supports(Feature::DollarParameters));
+
+        let info = server_info(None);
+        assert_eq!(None, info.version);
+        assert_eq!("$id", info.parameter("id"));
+    }
+}
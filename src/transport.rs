@@ -0,0 +1,1134 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::io;
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use hyper;
+use hyper::client::RedirectPolicy;
+use hyper::client::pool::{self, Pool};
+use hyper::header::{Authorization, Connection, Headers, Location};
+use hyper::method::Method;
+use hyper::net::{HttpStream, HttpsConnector, NetworkConnector, NetworkStream, SslClient};
+use hyper::status::{StatusClass, StatusCode};
+use hyper::Url;
+use native_tls::{self, Certificate, HandshakeError, Identity, TlsConnector};
+pub use types::Error;
+use types::ConnectError;
+use auth::AuthProvider;
+
+#[cfg(feature = "async")] use bytes::Bytes;
+#[cfg(feature = "async")] use futures::future::{self, BoxFuture, FutureExt, TryFutureExt};
+#[cfg(feature = "async")] use http;
+#[cfg(feature = "async")] use http_body_util::{BodyExt, Full};
+#[cfg(feature = "async")] use hyper_util::client::legacy::Client as LegacyClient;
+#[cfg(feature = "async")] use hyper_util::client::legacy::connect::HttpConnector as AsyncHttpConnector;
+#[cfg(feature = "async")] use hyper_tls::HttpsConnector as AsyncHttpsConnector;
+#[cfg(feature = "async")] use hyper_util::client::legacy::connect::{Connected, Connection as AsyncConnection};
+#[cfg(feature = "async")] use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+#[cfg(feature = "async")] use std::error;
+#[cfg(feature = "async")] use std::future::Future;
+#[cfg(feature = "async")] use std::pin::Pin;
+#[cfg(feature = "async")] use std::task::{Context, Poll};
+#[cfg(feature = "async")] use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+#[cfg(feature = "async")] use tower_service::Service;
+#[cfg(feature = "async")] use tokio;
+#[cfg(feature = "async")] use tokio_native_tls;
+
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: Method,
+    pub uri: String,
+    pub headers: Headers,
+    pub body: Option<String>,
+    // Headers carrying credentials, they are not sent along when a redirect leads to another host.
+    pub credential_headers: Vec<String>,
+}
+
+impl Request {
+    pub fn authenticate(&mut self, auth: &dyn AuthProvider) {
+        auth.apply(&mut self.headers);
+        for name in auth.header_names() {
+            if !self.credential_headers.contains(&name) {
+                self.credential_headers.push(name);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: StatusCode,
+    body: Cursor<Vec<u8>>,
+}
+
+impl Response {
+    pub fn new(status: StatusCode, body: String) -> Response {
+        Response {
+            status: status,
+            body: Cursor::new(body.into_bytes()),
+        }
+    }
+}
+
+impl Read for Response {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.body.read(buf)
+    }
+}
+
+pub trait Transport: Send + Sync {
+    fn send(&self, request: Request) -> Result<Response, Error>;
+}
+
+// Connection settings of the HTTP transports, `None` timeouts wait forever.
+#[derive(Clone, Debug)]
+pub struct HttpConfig {
+    // Idle connections kept per host.
+    pub pool_max_idle: usize,
+    pub idle_timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    pub read_timeout: Option<Duration>,
+    pub write_timeout: Option<Duration>,
+    pub max_redirects: u32,
+    // Without keep-alive every request is sent on a new connection.
+    pub keep_alive: bool,
+    pub tls: TlsConfig,
+}
+
+impl HttpConfig {
+    pub fn new() -> HttpConfig {
+        HttpConfig {
+            pool_max_idle: 5,
+            idle_timeout: None,
+            connect_timeout: None,
+            read_timeout: None,
+            write_timeout: None,
+            max_redirects: 10,
+            keep_alive: true,
+            tls: TlsConfig::new(),
+        }
+    }
+
+    // A pool without idle connections is the same as no keep-alive.
+    fn is_keep_alive(&self) -> bool {
+        self.keep_alive && self.pool_max_idle > 0
+    }
+}
+
+// TLS settings of https urls, by default only the system's trusted roots are accepted.
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    // PEM file with one or more additional trusted CA certificates.
+    pub ca_file: Option<String>,
+    // PEM files of the client certificate and of its PKCS #8 private key.
+    pub client_certificate: Option<(String, String)>,
+    pub built_in_roots: bool,
+    // Accepts any server certificate for any host name, only meant for development.
+    pub accept_invalid_certs: bool,
+}
+
+impl TlsConfig {
+    pub fn new() -> TlsConfig {
+        TlsConfig {
+            ca_file: None,
+            client_certificate: None,
+            built_in_roots: true,
+            accept_invalid_certs: false,
+        }
+    }
+
+    pub fn build_connector(&self) -> Result<TlsConnector, Error> {
+        let mut builder = TlsConnector::builder();
+        builder.disable_built_in_roots(!self.built_in_roots);
+        builder.danger_accept_invalid_certs(self.accept_invalid_certs);
+        builder.danger_accept_invalid_hostnames(self.accept_invalid_certs);
+
+        if let Some(ref ca_file) = self.ca_file {
+            let pem = match read_file(ca_file) {
+                Ok(pem) => pem,
+                Err(err) => return Err(err),
+            };
+            let certificates = match Certificate::stack_from_pem(&pem) {
+                Ok(certificates) => certificates,
+                Err(err) => return Err(Error::from(err)),
+            };
+            for certificate in certificates {
+                builder.add_root_certificate(certificate);
+            }
+        }
+
+        if let Some((ref certificate_file, ref key_file)) = self.client_certificate {
+            let certificate = match read_file(certificate_file) {
+                Ok(certificate) => certificate,
+                Err(err) => return Err(err),
+            };
+            let key = match read_file(key_file) {
+                Ok(key) => key,
+                Err(err) => return Err(err),
+            };
+            match Identity::from_pkcs8(&certificate, &key) {
+                Ok(identity) => builder.identity(identity),
+                Err(err) => return Err(Error::from(err)),
+            };
+        }
+
+        match builder.build() {
+            Ok(connector) => Ok(connector),
+            Err(err) => Err(Error::from(err)),
+        }
+    }
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, Error> {
+    match fs::read(path) {
+        Ok(content) => Ok(content),
+        Err(err) => Err(Error::from(io::Error::new(err.kind(), format!("{}: {}", path, err)))),
+    }
+}
+
+struct HttpConnector {
+    connect_timeout: Option<Duration>,
+}
+
+impl NetworkConnector for HttpConnector {
+    type Stream = HttpStream;
+
+    fn connect(&self, host: &str, port: u16, scheme: &str) -> hyper::Result<HttpStream> {
+        if scheme != "http" {
+            return Err(hyper::Error::from(io::Error::new(io::ErrorKind::InvalidInput, "Invalid scheme for Http")));
+        }
+
+        let timeout = match self.connect_timeout {
+            Some(timeout) => timeout,
+            None => return match TcpStream::connect((host, port)) {
+                Ok(stream) => Ok(HttpStream(stream)),
+                Err(err) => Err(connect_failure(err)),
+            },
+        };
+
+        // Every resolved address gets the full timeout, the last error is reported if none of them answers.
+        let mut last_err = io::Error::new(io::ErrorKind::NotFound, format!("Could not resolve {}", host));
+        let addrs = match (host, port).to_socket_addrs() {
+            Ok(addrs) => addrs,
+            Err(err) => return Err(connect_failure(err)),
+        };
+        for addr in addrs {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(stream) => return Ok(HttpStream(stream)),
+                Err(err) => last_err = err,
+            }
+        }
+        Err(connect_failure(last_err))
+    }
+}
+
+// hyper only passes io errors through, the connect failure is carried inside of one and unwrapped by `from_hyper_error()`.
+fn connect_failure(err: io::Error) -> hyper::Error {
+    hyper::Error::from(io::Error::new(err.kind(), ConnectError(Box::new(err))))
+}
+
+fn from_hyper_error(err: hyper::Error) -> Error {
+    match err {
+        hyper::Error::Io(err) => if err.get_ref().map_or(false, |inner| inner.is::<ConnectError>()) {
+            Error::NetworkError(err.into_inner().unwrap())
+        } else {
+            Error::from(err)
+        },
+        err => Error::from(err),
+    }
+}
+
+// A TLS setup that failed with the config is reported by every https request.
+struct NativeTlsClient {
+    connector: Result<TlsConnector, String>,
+    handshake_timeout: Option<Duration>,
+}
+
+impl SslClient for NativeTlsClient {
+    type Stream = NativeTlsStream;
+
+    fn wrap_client(&self, stream: HttpStream, host: &str) -> hyper::Result<NativeTlsStream> {
+        let connector = match self.connector {
+            Ok(ref connector) => connector,
+            Err(ref err) => return Err(hyper::Error::Ssl(Box::new(io::Error::new(io::ErrorKind::InvalidInput, err.clone())))),
+        };
+
+        if let Err(err) = stream.0.set_read_timeout(self.handshake_timeout) {
+            return Err(hyper::Error::from(err));
+        }
+        match connector.connect(host, stream) {
+            Ok(stream) => Ok(NativeTlsStream(Arc::new(Mutex::new(stream)))),
+            Err(HandshakeError::Failure(err)) => Err(hyper::Error::Ssl(Box::new(err))),
+            Err(HandshakeError::WouldBlock(_)) => Err(hyper::Error::Ssl(Box::new(io::Error::new(io::ErrorKind::TimedOut, "TLS handshake timed out")))),
+        }
+    }
+}
+
+// hyper 0.10 needs cloneable streams, the TLS session is shared behind a lock.
+#[derive(Clone)]
+struct NativeTlsStream(Arc<Mutex<native_tls::TlsStream<HttpStream>>>);
+
+impl Read for NativeTlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().read(buf)
+    }
+}
+
+impl Write for NativeTlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().unwrap().flush()
+    }
+}
+
+impl NetworkStream for NativeTlsStream {
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        self.0.lock().unwrap().get_mut().peer_addr()
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.0.lock().unwrap().get_ref().set_read_timeout(dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.0.lock().unwrap().get_ref().set_write_timeout(dur)
+    }
+
+    fn close(&mut self, how: Shutdown) -> io::Result<()> {
+        let mut stream = self.0.lock().unwrap();
+        let _ = stream.shutdown();
+        stream.get_mut().close(how)
+    }
+}
+
+pub struct HyperTransport {
+    // Each request takes its own handle, replacing the pool doesn't wait for the requests in flight.
+    client: RwLock<Arc<hyper::Client>>,
+    config: HttpConfig,
+    last_used: Mutex<Instant>,
+}
+
+impl HyperTransport {
+    pub fn new() -> HyperTransport {
+        HyperTransport::with_config(HttpConfig::new())
+    }
+
+    pub fn with_config(config: HttpConfig) -> HyperTransport {
+        HyperTransport {
+            client: RwLock::new(Arc::new(build_client(&config))),
+            config: config,
+            last_used: Mutex::new(Instant::now()),
+        }
+    }
+
+    // The client is used as it is: its own redirect policy applies, the pool is never reset.
+    pub fn with_client(client: hyper::Client) -> HyperTransport {
+        let mut config = HttpConfig::new();
+        config.max_redirects = 0;
+        HyperTransport {
+            client: RwLock::new(Arc::new(client)),
+            config: config,
+            last_used: Mutex::new(Instant::now()),
+        }
+    }
+
+    // hyper's pool has no idle timeout: once the transport has not been used for that long every pooled
+    // connection is stale, so the pool is replaced.
+    fn expire_idle_connections(&self) {
+        let idle = {
+            let mut last_used = self.last_used.lock().unwrap();
+            let idle = last_used.elapsed();
+            *last_used = Instant::now();
+            idle
+        };
+        if let Some(idle_timeout) = self.config.idle_timeout {
+            if idle >= idle_timeout {
+                debug!("Connection pool has been idle for {:?}, dropping its connections", idle);
+                let client = build_client(&self.config);
+                *self.client.write().unwrap() = Arc::new(client);
+            }
+        }
+    }
+}
+
+fn build_client(config: &HttpConfig) -> hyper::Client {
+    let tls = match config.tls.build_connector() {
+        Ok(tls) => Ok(tls),
+        Err(err) => {
+            error!("TLS could not be set up, https requests will fail: {}", err);
+            Err(err.to_string())
+        },
+    };
+    let ssl = NativeTlsClient {
+        connector: tls,
+        handshake_timeout: config.connect_timeout,
+    };
+    let connector = HttpsConnector::with_connector(ssl, HttpConnector {
+        connect_timeout: config.connect_timeout,
+    });
+    let mut client = if config.is_keep_alive() {
+        hyper::Client::with_connector(Pool::with_connector(pool::Config { max_idle: config.pool_max_idle }, connector))
+    } else {
+        hyper::Client::with_connector(connector)
+    };
+    client.set_read_timeout(config.read_timeout);
+    client.set_write_timeout(config.write_timeout);
+    // Redirects are followed by the transport, hyper can't limit their number.
+    client.set_redirect_policy(RedirectPolicy::FollowNone);
+    client
+}
+
+impl Transport for HyperTransport {
+    fn send(&self, mut request: Request) -> Result<Response, Error> {
+        self.expire_idle_connections();
+
+        if !self.config.is_keep_alive() {
+            request.headers.set(Connection::close());
+        }
+
+        let client = self.client.read().unwrap().clone();
+        let mut redirects = 0;
+        loop {
+            let mut builder = client.request(request.method.clone(), &*request.uri).headers(request.headers.clone());
+            if let Some(ref body) = request.body {
+                builder = builder.body(&**body);
+            }
+
+            let mut res = match builder.send() {
+                Ok(res) => res,
+                Err(err) => return Err(from_hyper_error(err)),
+            };
+
+            let location = match res.headers.get::<Location>() {
+                Some(location) if res.status.class() == StatusClass::Redirection && redirects < self.config.max_redirects => location.to_string(),
+                _ => {
+                    let mut res_raw = String::new();
+                    if let Err(err) = res.read_to_string(&mut res_raw) {
+                        return Err(Error::from(err));
+                    }
+                    return Ok(Response::new(res.status, res_raw));
+                },
+            };
+
+            if let Err(err) = follow_redirect(res.status, &location, &mut request) {
+                return Err(err);
+            }
+            redirects += 1;
+        }
+    }
+}
+
+// Points the request to the location of a redirect response, the same way for both transports.
+fn follow_redirect(status: StatusCode, location: &str, request: &mut Request) -> Result<(), Error> {
+    let current_url = match Url::parse(&request.uri) {
+        Ok(url) => url,
+        Err(err) => return Err(Error::DataError(Box::new(err))),
+    };
+    let next_url = match current_url.join(location) {
+        Ok(url) => url,
+        Err(err) => return Err(Error::DataError(Box::new(err))),
+    };
+
+    // Same as browsers: 303, and 301/302 after a POST, continue with a GET.
+    if status == StatusCode::SeeOther || (request.method == Method::Post && (status == StatusCode::MovedPermanently || status == StatusCode::Found)) {
+        request.method = Method::Get;
+        request.body = None;
+    }
+    if next_url.host_str() != current_url.host_str() || next_url.port_or_known_default() != current_url.port_or_known_default() {
+        request.headers.remove::<Authorization<String>>();
+        for name in &request.credential_headers {
+            request.headers.remove_raw(name);
+        }
+    }
+
+    info!("Following redirect ({}) to {}", status, next_url);
+    request.uri = next_url.to_string();
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+pub trait AsyncTransport: Send + Sync {
+    fn send_async(&self, request: Request) -> BoxFuture<'static, Result<Response, Error>>;
+}
+
+// Non-blocking transport on hyper 1.x, it has to be used from within a tokio runtime.
+// Redirects are followed like by the blocking transport, the read timeout limits the whole request.
+#[cfg(feature = "async")]
+pub struct HyperAsyncTransport {
+    client: AsyncClient,
+    config: HttpConfig,
+    tls_error: Option<String>,
+}
+
+#[cfg(feature = "async")]
+type AsyncClient = LegacyClient<AsyncHttpsConnector<WriteTimeoutConnector>, Full<Bytes>>;
+
+#[cfg(feature = "async")]
+impl HyperAsyncTransport {
+    pub fn new() -> HyperAsyncTransport {
+        HyperAsyncTransport::with_config(HttpConfig::new())
+    }
+
+    pub fn with_config(config: HttpConfig) -> HyperAsyncTransport {
+        let mut http = AsyncHttpConnector::new();
+        http.set_connect_timeout(config.connect_timeout);
+        http.enforce_http(false);
+        let http = WriteTimeoutConnector {
+            http: http,
+            write_timeout: config.write_timeout,
+        };
+
+        let (connector, tls_error) = match config.tls.build_connector() {
+            Ok(tls) => (AsyncHttpsConnector::from((http, tokio_native_tls::TlsConnector::from(tls))), None),
+            Err(err) => {
+                error!("TLS could not be set up, https requests will fail: {}", err);
+                (AsyncHttpsConnector::new_with_connector(http), Some(err.to_string()))
+            },
+        };
+
+        let client = LegacyClient::builder(TokioExecutor::new())
+            .pool_timer(TokioTimer::new())
+            .pool_max_idle_per_host(if config.is_keep_alive() { config.pool_max_idle } else { 0 })
+            .pool_idle_timeout(config.idle_timeout)
+            .build(connector);
+
+        HyperAsyncTransport {
+            client: client,
+            config: config,
+            tls_error: tls_error,
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncTransport for HyperAsyncTransport {
+    fn send_async(&self, mut request: Request) -> BoxFuture<'static, Result<Response, Error>> {
+        if !self.config.is_keep_alive() {
+            request.headers.set(Connection::close());
+        }
+        if let Some(ref err) = self.tls_error {
+            if request.uri.starts_with("https:") {
+                return future::ready(Err(Error::from(io::Error::new(io::ErrorKind::InvalidInput, err.clone())))).boxed();
+            }
+        }
+
+        let res = send_following_redirects_async(self.client.clone(), request, self.config.max_redirects);
+
+        match self.config.read_timeout {
+            // The timer is created lazily, the future may be built outside of the runtime.
+            Some(timeout) => future::lazy(move |_| tokio::time::timeout(timeout, res))
+                .flatten()
+                .map(|res| match res {
+                    Ok(res) => res,
+                    Err(_) => Err(Error::from(io::Error::new(io::ErrorKind::TimedOut, "request timed out"))),
+                })
+                .boxed(),
+            None => res.boxed(),
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+fn send_following_redirects_async(client: AsyncClient, mut request: Request, redirects_left: u32) -> BoxFuture<'static, Result<Response, Error>> {
+    let mut builder = http::Request::builder().method(request.method.as_ref()).uri(&*request.uri);
+    for header in request.headers.iter() {
+        builder = builder.header(header.name(), header.value_string());
+    }
+    let req = match builder.body(Full::new(Bytes::from(request.body.clone().unwrap_or(String::new())))) {
+        Ok(req) => req,
+        Err(err) => return future::ready(Err(Error::NetworkError(Box::new(err)))).boxed(),
+    };
+
+    client.request(req)
+        .map_err(|err| if err.is_connect() {
+            Error::from_connect_failure(err)
+        } else {
+            Error::NetworkError(Box::new(err))
+        })
+        .and_then(move |res| {
+            let status = StatusCode::from_u16(res.status().as_u16());
+            let location = match res.headers().get(http::header::LOCATION) {
+                Some(location) if status.class() == StatusClass::Redirection && redirects_left > 0 => location.to_str().ok().map(str::to_string),
+                _ => None,
+            };
+            if let Some(location) = location {
+                return match follow_redirect(status, &location, &mut request) {
+                    Ok(_) => send_following_redirects_async(client, request, redirects_left - 1),
+                    Err(err) => future::ready(Err(err)).boxed(),
+                };
+            }
+
+            res.into_body().collect()
+                .map_err(|err| Error::NetworkError(Box::new(err)))
+                .map_ok(move |body| Response::new(status, String::from_utf8_lossy(&body.to_bytes()).into_owned()))
+                .boxed()
+        })
+        .boxed()
+}
+
+// hyper 1.x has no write timeout, the connector wraps every connection into a stream enforcing it.
+#[cfg(feature = "async")]
+#[derive(Clone)]
+struct WriteTimeoutConnector {
+    http: AsyncHttpConnector,
+    write_timeout: Option<Duration>,
+}
+
+#[cfg(feature = "async")]
+impl Service<http::Uri> for WriteTimeoutConnector {
+    type Response = TokioIo<WriteTimeoutStream>;
+    type Error = Box<dyn error::Error + Send + Sync>;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.http.poll_ready(cx).map_err(From::from)
+    }
+
+    fn call(&mut self, uri: http::Uri) -> Self::Future {
+        let write_timeout = self.write_timeout;
+        self.http.call(uri)
+            .map_ok(move |stream| TokioIo::new(WriteTimeoutStream {
+                stream: stream.into_inner(),
+                write_timeout: write_timeout,
+                timer: None,
+            }))
+            .map_err(From::from)
+            .boxed()
+    }
+}
+
+// Writes (and flushes) not making any progress for the write timeout fail with `TimedOut`.
+#[cfg(feature = "async")]
+struct WriteTimeoutStream {
+    stream: tokio::net::TcpStream,
+    write_timeout: Option<Duration>,
+    // Started by the first pending write, reset by any progress.
+    timer: Option<Pin<Box<tokio::time::Sleep>>>,
+}
+
+#[cfg(feature = "async")]
+impl WriteTimeoutStream {
+    fn poll_timeout<T>(&mut self, cx: &mut Context, res: Poll<io::Result<T>>) -> Poll<io::Result<T>> {
+        if res.is_ready() {
+            self.timer = None;
+            return res;
+        }
+        let write_timeout = match self.write_timeout {
+            Some(write_timeout) => write_timeout,
+            None => return res,
+        };
+        let timer = self.timer.get_or_insert_with(|| Box::pin(tokio::time::sleep(write_timeout)));
+        match timer.as_mut().poll(cx) {
+            Poll::Ready(_) => {
+                self.timer = None;
+                Poll::Ready(Err(io::Error::new(io::ErrorKind::TimedOut, "write timed out")))
+            },
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncRead for WriteTimeoutStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context, buf: &mut ReadBuf) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_read(cx, buf)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncWrite for WriteTimeoutStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.stream).poll_write(cx, buf);
+        this.poll_timeout(cx, res)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.stream).poll_flush(cx);
+        this.poll_timeout(cx, res)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncConnection for WriteTimeoutStream {
+    fn connected(&self) -> Connected {
+        self.stream.connected()
+    }
+}
+
+struct MockState {
+    responses: VecDeque<Result<(StatusCode, String), Error>>,
+    requests: Vec<Request>,
+}
+
+// Replays queued responses in order and records every request. Clones share the same state so
+// a handle can be kept for assertions after the transport is moved into a client.
+#[derive(Clone)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockTransport {
+    pub fn new() -> MockTransport {
+        MockTransport {
+            state: Arc::new(Mutex::new(MockState {
+                responses: VecDeque::new(),
+                requests: Vec::new(),
+            })),
+        }
+    }
+
+    pub fn push_response(&self, status: StatusCode, body: &str) -> &MockTransport {
+        self.state.lock().unwrap().responses.push_back(Ok((status, body.to_string())));
+        self
+    }
+
+    pub fn push_error(&self, error: Error) -> &MockTransport {
+        self.state.lock().unwrap().responses.push_back(Err(error));
+        self
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    pub fn pending_responses(&self) -> usize {
+        self.state.lock().unwrap().responses.len()
+    }
+}
+
+impl Transport for MockTransport {
+    fn send(&self, request: Request) -> Result<Response, Error> {
+        let mut state = self.state.lock().unwrap();
+        state.requests.push(request);
+        match state.responses.pop_front() {
+            Some(Ok((status, body))) => Ok(Response::new(status, body)),
+            Some(Err(err)) => Err(err),
+            None => {
+                warn!("Mock transport has no response queued");
+                Err(Error::from(io::Error::new(io::ErrorKind::NotConnected, "mock transport has no response queued")))
+            },
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncTransport for MockTransport {
+    fn send_async(&self, request: Request) -> BoxFuture<'static, Result<Response, Error>> {
+        future::ready(self.send(request)).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::io;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::fs;
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use hyper::method::Method;
+    use hyper::status::StatusCode;
+    use hyper::header::Headers;
+    use native_tls::{Identity, TlsAcceptor};
+    use transport::{HttpConfig, HyperTransport, MockTransport, Request, TlsConfig, Transport};
+    pub use types::Error;
+
+    struct MockHttpServer {
+        address: String,
+        connections: Arc<Mutex<usize>>,
+        requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    // Local HTTP server answering requests with the scripted raw responses, `None` never answers.
+    fn start_mock_http_server(responses: Vec<Option<&str>>) -> MockHttpServer {
+        start_mock_server(responses, None)
+    }
+
+    // Same over TLS, with the certificate of localhost signed by the test CA.
+    fn start_mock_https_server(responses: Vec<Option<&str>>) -> MockHttpServer {
+        let identity = Identity::from_pkcs8(&fs::read(get_fixture("localhost.crt")).unwrap(), &fs::read(get_fixture("localhost.key")).unwrap()).unwrap();
+        start_mock_server(responses, Some(TlsAcceptor::new(identity).unwrap()))
+    }
+
+    fn start_mock_server(responses: Vec<Option<&str>>, tls: Option<TlsAcceptor>) -> MockHttpServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = match tls {
+            Some(_) => format!("https://localhost:{}", listener.local_addr().unwrap().port()),
+            None => format!("http://{}", listener.local_addr().unwrap()),
+        };
+        let responses: Arc<Mutex<VecDeque<Option<String>>>> = Arc::new(Mutex::new(responses.into_iter().map(|res| res.map(str::to_string)).collect()));
+        let connections = Arc::new(Mutex::new(0));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let server_connections = connections.clone();
+        let server_requests = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => return,
+                };
+                *server_connections.lock().unwrap() += 1;
+                let responses = responses.clone();
+                let requests = server_requests.clone();
+                let tls = tls.clone();
+                thread::spawn(move || match tls {
+                    Some(tls) => if let Ok(stream) = tls.accept(stream) {
+                        serve_mock_http_connection(stream, responses, requests)
+                    },
+                    None => serve_mock_http_connection(stream, responses, requests),
+                });
+            }
+        });
+
+        MockHttpServer {
+            address: address,
+            connections: connections,
+            requests: requests,
+        }
+    }
+
+    fn serve_mock_http_connection<S: Read + Write>(stream: S, responses: Arc<Mutex<VecDeque<Option<String>>>>, requests: Arc<Mutex<Vec<String>>>) {
+        let mut reader = BufReader::new(stream);
+        loop {
+            let mut head = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                match reader.read_line(&mut line) {
+                    Ok(0) | Err(_) => return,
+                    Ok(_) => {},
+                }
+                if line.to_lowercase().starts_with("content-length:") {
+                    content_length = line[15..].trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                head.push_str(&line);
+            }
+            let mut body = vec![0; content_length];
+            let _ = reader.read_exact(&mut body);
+            requests.lock().unwrap().push(head.clone());
+
+            let response = responses.lock().unwrap().pop_front().unwrap_or(None);
+            match response {
+                Some(response) => {
+                    let _ = reader.get_mut().write_all(response.as_bytes()).and_then(|_| reader.get_mut().flush());
+                },
+                None => {
+                    thread::sleep(Duration::from_secs(5));
+                    return;
+                },
+            }
+            if head.to_lowercase().contains("connection: close") {
+                return;
+            }
+        }
+    }
+
+    fn get_fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/tls/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    fn get_http_config() -> HttpConfig {
+        let mut config = HttpConfig::new();
+        config.connect_timeout = Some(Duration::from_secs(1));
+        config.read_timeout = Some(Duration::from_secs(1));
+        config
+    }
+
+    fn get_request(uri: &str) -> Request {
+        Request {
+            method: Method::Get,
+            uri: uri.to_string(),
+            headers: Headers::new(),
+            body: None,
+            credential_headers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_mock_replays_in_order_and_records() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Ok, "first");
+        transport.push_response(StatusCode::NoContent, "");
+
+        let mut res = transport.send(get_request("http://localhost:7474/a")).unwrap();
+        let mut res_raw = String::new();
+        let _ = res.read_to_string(&mut res_raw);
+        assert_eq!(StatusCode::Ok, res.status);
+        assert_eq!("first", res_raw);
+
+        let res = transport.clone().send(get_request("http://localhost:7474/b")).unwrap();
+        assert_eq!(StatusCode::NoContent, res.status);
+
+        let requests = transport.requests();
+        assert_eq!(2, requests.len());
+        assert_eq!("http://localhost:7474/a", requests[0].uri);
+        assert_eq!("http://localhost:7474/b", requests[1].uri);
+        assert_eq!(0, transport.pending_responses());
+    }
+
+    #[test]
+    fn test_mock_errors_when_exhausted() {
+        let transport = MockTransport::new();
+        transport.push_error(Error::from(io::Error::new(io::ErrorKind::ConnectionRefused, "refused")));
+
+        assert!(transport.send(get_request("http://localhost:7474/a")).is_err());
+        assert!(transport.send(get_request("http://localhost:7474/b")).is_err());
+        assert_eq!(2, transport.requests().len());
+    }
+
+    #[test]
+    fn test_hyper_transport_keep_alive_with_mock_server() {
+        let ok = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let server = start_mock_http_server(vec![Some(ok), Some(ok), Some(ok), Some(ok)]);
+        let uri = format!("{}/db/data", server.address);
+
+        let transport = HyperTransport::with_config(get_http_config());
+        assert_eq!(StatusCode::Ok, transport.send(get_request(&uri)).unwrap().status);
+        assert_eq!(StatusCode::Ok, transport.send(get_request(&uri)).unwrap().status);
+        assert_eq!(1, *server.connections.lock().unwrap());
+
+        let mut config = get_http_config();
+        config.keep_alive = false;
+        let transport = HyperTransport::with_config(config);
+        assert_eq!(StatusCode::Ok, transport.send(get_request(&uri)).unwrap().status);
+        assert_eq!(StatusCode::Ok, transport.send(get_request(&uri)).unwrap().status);
+        assert_eq!(3, *server.connections.lock().unwrap());
+        assert!(server.requests.lock().unwrap()[3].to_lowercase().contains("connection: close"));
+    }
+
+    #[test]
+    fn test_hyper_transport_idle_timeout_with_mock_server() {
+        let ok = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let server = start_mock_http_server(vec![Some(ok), Some(ok)]);
+        let uri = format!("{}/db/data", server.address);
+
+        let mut config = get_http_config();
+        config.idle_timeout = Some(Duration::from_millis(50));
+        let transport = HyperTransport::with_config(config);
+        assert!(transport.send(get_request(&uri)).is_ok());
+        thread::sleep(Duration::from_millis(100));
+        assert!(transport.send(get_request(&uri)).is_ok());
+        assert_eq!(2, *server.connections.lock().unwrap());
+    }
+
+    #[test]
+    fn test_hyper_transport_read_timeout_with_mock_server() {
+        let server = start_mock_http_server(vec![None]);
+        let mut config = get_http_config();
+        config.read_timeout = Some(Duration::from_millis(100));
+        let transport = HyperTransport::with_config(config);
+
+        let started = Instant::now();
+        match transport.send(get_request(&format!("{}/db/data", server.address))) {
+            Err(Error::NetworkError(_)) => {},
+            _ => panic!("Expected network error"),
+        }
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_hyper_transport_follows_limited_redirects_with_mock_server() {
+        let redirect = "HTTP/1.1 302 Found\r\nLocation: /db/data/\r\nContent-Length: 0\r\n\r\n";
+        let ok = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let server = start_mock_http_server(vec![Some(redirect), Some(ok), Some(redirect)]);
+        let uri = format!("{}/db/data", server.address);
+
+        let transport = HyperTransport::with_config(get_http_config());
+        let mut res = transport.send(get_request(&uri)).unwrap();
+        let mut res_raw = String::new();
+        let _ = res.read_to_string(&mut res_raw);
+        assert_eq!(StatusCode::Ok, res.status);
+        assert_eq!("ok", res_raw);
+        assert!(server.requests.lock().unwrap()[1].starts_with("GET /db/data/ HTTP/1.1"));
+
+        let mut config = get_http_config();
+        config.max_redirects = 0;
+        let transport = HyperTransport::with_config(config);
+        assert_eq!(StatusCode::Found, transport.send(get_request(&uri)).unwrap().status);
+    }
+
+    #[test]
+    fn test_hyper_transport_drops_credentials_on_cross_host_redirect_with_mock_server() {
+        use auth::HeaderAuth;
+
+        let ok = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let server = start_mock_http_server(vec![Some(ok), Some(ok)]);
+        let same_host_redirect = "HTTP/1.1 307 Temporary Redirect\r\nLocation: /db/data/\r\nContent-Length: 0\r\n\r\n";
+        let cross_host_redirect = format!("HTTP/1.1 307 Temporary Redirect\r\nLocation: {}/db/data/\r\nContent-Length: 0\r\n\r\n", server.address);
+        let other_server = start_mock_http_server(vec![Some(same_host_redirect), Some(&cross_host_redirect)]);
+        let auth = HeaderAuth::new("X-Api-Key".to_string(), "secret-key".to_string());
+
+        let transport = HyperTransport::with_config(get_http_config());
+        let mut request = get_request(&format!("{}/db/data", other_server.address.replace("127.0.0.1", "localhost")));
+        request.authenticate(&auth);
+        assert_eq!(StatusCode::Ok, transport.send(request.clone()).unwrap().status);
+
+        // The same host gets the key again, the other host doesn't.
+        let other_requests = other_server.requests.lock().unwrap().clone();
+        assert_eq!(2, other_requests.len());
+        assert!(other_requests.iter().all(|req| req.to_lowercase().contains("x-api-key: secret-key")));
+        assert!(!server.requests.lock().unwrap()[0].contains("secret-key"));
+
+        #[cfg(feature = "async")]
+        {
+            use transport::{AsyncTransport, HyperAsyncTransport};
+
+            let other_server = start_mock_http_server(vec![Some(&cross_host_redirect)]);
+            let mut request = get_request(&format!("{}/db/data", other_server.address.replace("127.0.0.1", "localhost")));
+            request.authenticate(&auth);
+            let runtime = ::tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            let transport = HyperAsyncTransport::with_config(get_http_config());
+            assert_eq!(StatusCode::Ok, runtime.block_on(transport.send_async(request)).unwrap().status);
+            assert!(other_server.requests.lock().unwrap()[0].to_lowercase().contains("x-api-key: secret-key"));
+            assert!(!server.requests.lock().unwrap()[1].contains("secret-key"));
+        }
+    }
+
+    #[test]
+    fn test_hyper_transport_https_with_mock_server() {
+        let ok = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let server = start_mock_https_server(vec![Some(ok), Some(ok), Some(ok)]);
+        let uri = format!("{}/db/data", server.address);
+
+        let transport = HyperTransport::with_config(get_http_config());
+        assert!(transport.send(get_request(&uri)).is_err());
+
+        let mut config = get_http_config();
+        config.tls.ca_file = Some(get_fixture("ca.crt"));
+        let transport = HyperTransport::with_config(config.clone());
+        let mut res = transport.send(get_request(&uri)).unwrap();
+        let mut res_raw = String::new();
+        let _ = res.read_to_string(&mut res_raw);
+        assert_eq!("ok", res_raw);
+
+        config.tls.client_certificate = Some((get_fixture("client.crt"), get_fixture("client.key")));
+        let transport = HyperTransport::with_config(config);
+        assert_eq!(StatusCode::Ok, transport.send(get_request(&uri)).unwrap().status);
+
+        let mut config = get_http_config();
+        config.tls.accept_invalid_certs = true;
+        let transport = HyperTransport::with_config(config);
+        assert_eq!(StatusCode::Ok, transport.send(get_request(&uri)).unwrap().status);
+    }
+
+    #[test]
+    fn test_tls_config_errors() {
+        let mut config = TlsConfig::new();
+        config.ca_file = Some(get_fixture("missing.crt"));
+        let err = config.build_connector().err().unwrap();
+        assert!(err.to_string().contains("missing.crt"));
+
+        let mut config = TlsConfig::new();
+        config.client_certificate = Some((get_fixture("client.crt"), get_fixture("ca.crt")));
+        assert!(config.build_connector().is_err());
+
+        let mut http_config = get_http_config();
+        http_config.tls.ca_file = Some(get_fixture("missing.crt"));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("https://localhost:{}/db/data", listener.local_addr().unwrap().port());
+        let transport = HyperTransport::with_config(http_config);
+        assert!(transport.send(get_request(&uri)).unwrap_err().to_string().contains("missing.crt"));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_hyper_async_transport_follows_limited_redirects_with_mock_server() {
+        use hyper::header::Authorization;
+        use transport::{AsyncTransport, HyperAsyncTransport};
+
+        let redirect = "HTTP/1.1 302 Found\r\nLocation: /db/data/\r\nContent-Length: 0\r\n\r\n";
+        let ok = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let server = start_mock_http_server(vec![Some(redirect), Some(ok), Some(redirect), Some(ok)]);
+        let runtime = ::tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+
+        // Another host: the credentials are not sent along.
+        let cross_host_redirect = format!("HTTP/1.1 307 Temporary Redirect\r\nLocation: {}/db/data/\r\nContent-Length: 0\r\n\r\n", server.address);
+        let other_server = start_mock_http_server(vec![Some(&cross_host_redirect)]);
+
+        let transport = HyperAsyncTransport::with_config(get_http_config());
+        let mut res = runtime.block_on(transport.send_async(get_request(&format!("{}/db/data", server.address)))).unwrap();
+        let mut res_raw = String::new();
+        let _ = res.read_to_string(&mut res_raw);
+        assert_eq!(StatusCode::Ok, res.status);
+        assert_eq!("ok", res_raw);
+        assert!(server.requests.lock().unwrap()[1].starts_with("GET /db/data/ HTTP/1.1"));
+
+        let mut config = get_http_config();
+        config.max_redirects = 0;
+        let transport = HyperAsyncTransport::with_config(config);
+        assert_eq!(StatusCode::Found, runtime.block_on(transport.send_async(get_request(&format!("{}/db/data", server.address)))).unwrap().status);
+
+        let transport = HyperAsyncTransport::with_config(get_http_config());
+        let mut request = get_request(&format!("{}/db/data", other_server.address.replace("127.0.0.1", "localhost")));
+        request.headers.set(Authorization("Basic bmVvNGo6c2VjcmV0".to_string()));
+        assert!(runtime.block_on(transport.send_async(request)).is_ok());
+        assert!(other_server.requests.lock().unwrap()[0].contains("Basic bmVvNGo6c2VjcmV0"));
+        assert!(!server.requests.lock().unwrap()[3].contains("Basic bmVvNGo6c2VjcmV0"));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_hyper_async_transport_write_timeout_with_mock_server() {
+        use transport::{AsyncTransport, HyperAsyncTransport};
+
+        // Accepts the connection and never reads from it.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/db/data", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let _stream = listener.accept();
+            thread::sleep(Duration::from_secs(5));
+        });
+        let runtime = ::tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+
+        let mut config = get_http_config();
+        config.read_timeout = None;
+        config.write_timeout = Some(Duration::from_millis(100));
+        let transport = HyperAsyncTransport::with_config(config);
+        let mut request = get_request(&uri);
+        request.method = Method::Post;
+        request.body = Some("x".repeat(64 * 1024 * 1024));
+
+        let started = Instant::now();
+        assert!(runtime.block_on(transport.send_async(request)).is_err());
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_hyper_async_transport_https_with_mock_server() {
+        use transport::{AsyncTransport, HyperAsyncTransport};
+
+        let ok = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let server = start_mock_https_server(vec![Some(ok)]);
+        let uri = format!("{}/db/data", server.address);
+        let runtime = ::tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+
+        let mut config = get_http_config();
+        config.tls.ca_file = Some(get_fixture("ca.crt"));
+        let transport = HyperAsyncTransport::with_config(config);
+        let res = runtime.block_on(transport.send_async(get_request(&uri)));
+        assert_eq!(StatusCode::Ok, res.unwrap().status);
+
+        let mut config = get_http_config();
+        config.tls.ca_file = Some(get_fixture("missing.crt"));
+        let transport = HyperAsyncTransport::with_config(config);
+        assert!(runtime.block_on(transport.send_async(get_request(&uri))).is_err());
+    }
+}
@@ -1,7 +1,389 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::Read;
+use hyper;
+use hyper::status::StatusCode;
+use native_tls;
+use rustc_serialize::json;
+use rustc_serialize::json::Json;
+use transport::Response;
+
 #[derive(Debug)]
 pub enum Error {
-    NetworkError,
-    ResponseError,
-    DataError,
+    NetworkError(Box<dyn error::Error + Send + Sync>),
+    ResponseError(ResponseError),
+    DataError(Box<dyn error::Error + Send + Sync>),
     IntegrityError,
+    // Invalid client configuration, eg. a malformed connection URI.
+    ConfigError(String),
+    // The server rejects every request until the initial password is changed, see `user::User::change_password()`.
+    PasswordChangeRequired(ResponseError),
+    // A node can't be deleted while it has relationships, see `node::Node::detach_delete()`.
+    NodeHasRelationships(ResponseError),
+}
+
+impl Error {
+    // Error of an unexpected response, recognising the state requiring a password change.
+    pub fn from_response(response: Response) -> Error {
+        Error::from(ResponseError::from_response(response))
+    }
+
+    pub fn status(&self) -> Option<StatusCode> {
+        match *self {
+            Error::ResponseError(ref err) => Some(err.status),
+            Error::PasswordChangeRequired(ref err) => Some(err.status),
+            Error::NodeHasRelationships(ref err) => Some(err.status),
+            _ => None,
+        }
+    }
+
+    pub fn neo4j_errors(&self) -> &[Neo4jError] {
+        match *self {
+            Error::ResponseError(ref err) => &err.errors,
+            Error::PasswordChangeRequired(ref err) => &err.errors,
+            Error::NodeHasRelationships(ref err) => &err.errors,
+            _ => &[],
+        }
+    }
+
+    pub fn has_neo4j_status(&self, classification: Classification, category: &str, title: &str) -> bool {
+        self.neo4j_errors().iter().any(|err| match err.status() {
+            Some(status) => status.classification == classification && status.category == category && status.title == title,
+            None => false,
+        })
+    }
+
+    pub fn is_transient(&self) -> bool {
+        self.neo4j_errors().iter().any(|err| err.is_transient())
+    }
+
+    // The connection could not be established, the request has not reached the server.
+    pub fn from_connect_failure<E: Into<Box<dyn error::Error + Send + Sync>>>(err: E) -> Error {
+        Error::NetworkError(Box::new(ConnectError(err.into())))
+    }
+
+    // Unlike other network errors, a failed connect is safe to retry even for requests that are not idempotent.
+    pub fn is_connect_failure(&self) -> bool {
+        match *self {
+            Error::NetworkError(ref err) => err.is::<ConnectError>(),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::NetworkError(ref err) => write!(f, "Network error: {}", err),
+            Error::ResponseError(ref err) => write!(f, "Response error: {}", err),
+            Error::DataError(ref err) => write!(f, "Data error: {}", err),
+            Error::IntegrityError => write!(f, "Integrity error: resource is not in a state that allows this operation"),
+            Error::ConfigError(ref err) => write!(f, "Configuration error: {}", err),
+            Error::PasswordChangeRequired(ref err) => write!(f, "Password change required: {}", err),
+            Error::NodeHasRelationships(ref err) => write!(f, "Node still has relationships: {}", err),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::NetworkError(ref err) => Some(&**err),
+            Error::ResponseError(ref err) => Some(err),
+            Error::DataError(ref err) => Some(&**err),
+            Error::IntegrityError => None,
+            Error::ConfigError(_) => None,
+            Error::PasswordChangeRequired(ref err) => Some(err),
+            Error::NodeHasRelationships(ref err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::NetworkError(Box::new(err))
+    }
+}
+
+impl From<hyper::Error> for Error {
+    fn from(err: hyper::Error) -> Error {
+        Error::NetworkError(Box::new(err))
+    }
+}
+
+impl From<native_tls::Error> for Error {
+    fn from(err: native_tls::Error) -> Error {
+        Error::NetworkError(Box::new(err))
+    }
+}
+
+impl From<ResponseError> for Error {
+    fn from(err: ResponseError) -> Error {
+        if err.is_password_change_required() {
+            Error::PasswordChangeRequired(err)
+        } else {
+            Error::ResponseError(err)
+        }
+    }
+}
+
+impl From<json::DecoderError> for Error {
+    fn from(err: json::DecoderError) -> Error {
+        Error::DataError(Box::new(err))
+    }
+}
+
+impl From<json::EncoderError> for Error {
+    fn from(err: json::EncoderError) -> Error {
+        Error::DataError(Box::new(err))
+    }
+}
+
+impl From<json::ParserError> for Error {
+    fn from(err: json::ParserError) -> Error {
+        Error::DataError(Box::new(err))
+    }
+}
+
+// Cause of the network errors raised before anything was sent, see `Error::is_connect_failure()`.
+#[derive(Debug)]
+pub struct ConnectError(pub Box<dyn error::Error + Send + Sync>);
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not connect: {}", self.0)
+    }
+}
+
+impl error::Error for ConnectError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&*self.0)
+    }
+}
+
+// Error object as Neo4j reports it, eg.: {"code": "Neo.ClientError.Schema.ConstraintValidationFailed", "message": "..."}.
+#[derive(RustcDecodable, Clone, Debug, PartialEq)]
+pub struct Neo4jError {
+    pub code: String,
+    pub message: String,
+}
+
+impl Neo4jError {
+    pub fn status(&self) -> Option<Neo4jStatus> {
+        Neo4jStatus::parse(&self.code)
+    }
+
+    pub fn is_transient(&self) -> bool {
+        match self.status() {
+            Some(status) => status.classification == Classification::TransientError,
+            None => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Classification {
+    ClientError,
+    ClientNotification,
+    TransientError,
+    DatabaseError,
+}
+
+// Parsed form of a status code: Neo.<Classification>.<Category>.<Title>.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Neo4jStatus {
+    pub classification: Classification,
+    pub category: String,
+    pub title: String,
+}
+
+impl Neo4jStatus {
+    pub fn parse(code: &str) -> Option<Neo4jStatus> {
+        let parts: Vec<&str> = code.split('.').collect();
+        if parts.len() != 4 || parts[0] != "Neo" {
+            return None;
+        }
+
+        let classification = match parts[1] {
+            "ClientError" => Classification::ClientError,
+            "ClientNotification" => Classification::ClientNotification,
+            "TransientError" => Classification::TransientError,
+            "DatabaseError" => Classification::DatabaseError,
+            _ => return None,
+        };
+
+        Some(Neo4jStatus {
+            classification: classification,
+            category: parts[2].to_string(),
+            title: parts[3].to_string(),
+        })
+    }
+}
+
+#[derive(RustcDecodable)]
+struct ErrorPayload {
+    errors: Option<Vec<Neo4jError>>,
+    message: Option<String>,
+    exception: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ResponseError {
+    pub status: StatusCode,
+    pub body: String,
+    pub errors: Vec<Neo4jError>,
+}
+
+impl ResponseError {
+    pub fn new(status: StatusCode, body: String) -> ResponseError {
+        let errors = match json::decode::<ErrorPayload>(&body) {
+            Ok(ErrorPayload { errors: Some(errors), .. }) => errors,
+            // Older servers only send the exception name and message.
+            Ok(ErrorPayload { errors: None, message: Some(message), exception }) => vec![Neo4jError {
+                code: exception.unwrap_or(String::new()),
+                message: message,
+            }],
+            _ => Vec::new(),
+        };
+
+        ResponseError {
+            status: status,
+            body: body,
+            errors: errors,
+        }
+    }
+
+    pub fn from_response(mut response: Response) -> ResponseError {
+        let mut res_raw = String::new();
+        let _ = response.read_to_string(&mut res_raw);
+        ResponseError::new(response.status, res_raw)
+    }
+
+    // REST answers 403 with a "password_change" link, Bolt fails with CredentialsExpired.
+    pub fn is_password_change_required(&self) -> bool {
+        let has_password_change_link = self.status == StatusCode::Forbidden && match Json::from_str(&self.body) {
+            Ok(Json::Object(ref body)) => body.contains_key("password_change"),
+            _ => false,
+        };
+        has_password_change_link || self.errors.iter().any(|err| err.code == "Neo.ClientError.Security.CredentialsExpired")
+    }
+}
+
+impl fmt::Display for ResponseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.status)?;
+        if self.errors.is_empty() {
+            if !self.body.is_empty() {
+                write!(f, " ({})", self.body)?;
+            }
+            return Ok(());
+        }
+        for err in &self.errors {
+            write!(f, " [{}] {}", err.code, err.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for ResponseError {}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use hyper::status::StatusCode;
+    use rustc_serialize::json;
+    use types::{Classification, Error, Neo4jError, Neo4jStatus, ResponseError};
+
+    #[test]
+    fn test_response_error_decodes_neo4j_errors() {
+        let err = ResponseError::new(StatusCode::Conflict, r#"{"errors":[{"code":"Neo.ClientError.Schema.ConstraintValidationFailed","message":"Node(1) already exists"}]}"#.to_string());
+        assert_eq!(StatusCode::Conflict, err.status);
+        assert_eq!(vec![Neo4jError {
+            code: "Neo.ClientError.Schema.ConstraintValidationFailed".to_string(),
+            message: "Node(1) already exists".to_string(),
+        }], err.errors);
+        assert_eq!("409 Conflict [Neo.ClientError.Schema.ConstraintValidationFailed] Node(1) already exists", format!("{}", err));
+    }
+
+    #[test]
+    fn test_response_error_legacy_and_empty_payload() {
+        let err = ResponseError::new(StatusCode::NotFound, r#"{"message":"Cannot find node with id [3] in database.","exception":"NodeNotFoundException"}"#.to_string());
+        assert_eq!("NodeNotFoundException", err.errors[0].code);
+
+        let err = ResponseError::new(StatusCode::Unauthorized, String::new());
+        assert!(err.errors.is_empty());
+        assert_eq!("401 Unauthorized", format!("{}", err));
+    }
+
+    #[test]
+    fn test_error_conversions() {
+        let err = Error::from(io::Error::new(io::ErrorKind::ConnectionRefused, "refused"));
+        assert_eq!("Network error: refused", format!("{}", err));
+        assert!(err.status().is_none());
+
+        let err = Error::from(json::decode::<Neo4jError>("{}").unwrap_err());
+        match err {
+            Error::DataError(_) => {},
+            _ => panic!("Expected data error"),
+        }
+
+        let err = Error::ResponseError(ResponseError::new(StatusCode::NotFound, String::new()));
+        assert_eq!(Some(StatusCode::NotFound), err.status());
+
+        let err = Error::from_connect_failure(io::Error::new(io::ErrorKind::ConnectionRefused, "refused"));
+        assert_eq!("Network error: Could not connect: refused", format!("{}", err));
+        assert!(err.is_connect_failure());
+        assert!(!Error::from(io::Error::new(io::ErrorKind::ConnectionReset, "reset")).is_connect_failure());
+    }
+
+    #[test]
+    fn test_neo4j_status_parse() {
+        assert_eq!(Some(Neo4jStatus {
+            classification: Classification::TransientError,
+            category: "Transaction".to_string(),
+            title: "DeadlockDetected".to_string(),
+        }), Neo4jStatus::parse("Neo.TransientError.Transaction.DeadlockDetected"));
+        assert_eq!(Classification::ClientError, Neo4jStatus::parse("Neo.ClientError.Schema.ConstraintValidationFailed").unwrap().classification);
+        assert_eq!(Classification::DatabaseError, Neo4jStatus::parse("Neo.DatabaseError.General.UnknownError").unwrap().classification);
+        assert!(Neo4jStatus::parse("NodeNotFoundException").is_none());
+        assert!(Neo4jStatus::parse("Neo.MadeUpError.Foo.Bar").is_none());
+    }
+
+    #[test]
+    fn test_error_classification() {
+        let err = Error::ResponseError(ResponseError::new(StatusCode::Ok, r#"{"errors":[{"code":"Neo.TransientError.Transaction.DeadlockDetected","message":"Deadlock"}]}"#.to_string()));
+        assert!(err.is_transient());
+        assert!(err.has_neo4j_status(Classification::TransientError, "Transaction", "DeadlockDetected"));
+        assert!(!err.has_neo4j_status(Classification::ClientError, "Schema", "ConstraintValidationFailed"));
+
+        let err = Error::ResponseError(ResponseError::new(StatusCode::Ok, r#"{"errors":[{"code":"Neo.ClientError.Schema.ConstraintValidationFailed","message":"Exists"}]}"#.to_string()));
+        assert!(!err.is_transient());
+        assert!(err.has_neo4j_status(Classification::ClientError, "Schema", "ConstraintValidationFailed"));
+
+        assert!(!Error::IntegrityError.is_transient());
+        assert!(Error::IntegrityError.neo4j_errors().is_empty());
+    }
+
+    #[test]
+    fn test_password_change_required() {
+        let err = Error::from(ResponseError::new(StatusCode::Forbidden, r#"{"password_change":"http://localhost:7474/user/neo4j/password","errors":[]}"#.to_string()));
+        match err {
+            Error::PasswordChangeRequired(_) => {},
+            _ => panic!("Unexpected error: {:?}", err),
+        }
+        assert!(err.to_string().starts_with("Password change required"));
+
+        let err = Error::from(ResponseError::new(StatusCode::Ok, r#"{"errors":[{"code":"Neo.ClientError.Security.CredentialsExpired","message":"Expired"}]}"#.to_string()));
+        match err {
+            Error::PasswordChangeRequired(_) => {},
+            _ => panic!("Unexpected error: {:?}", err),
+        }
+
+        let err = Error::from(ResponseError::new(StatusCode::Forbidden, r#"{"errors":[{"code":"Neo.ClientError.Security.Forbidden","message":"No"}]}"#.to_string()));
+        match err {
+            Error::ResponseError(_) => {},
+            _ => panic!("Unexpected error: {:?}", err),
+        }
+    }
 }
@@ -0,0 +1,176 @@
+use std::io::Read;
+use rustc_serialize::json;
+use hyper;
+use url::percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
+use client::RequestBuilder;
+use transport::Response;
+pub use types::Error;
+
+#[cfg(feature = "async")] use futures::future::{self, BoxFuture, FutureExt, TryFutureExt};
+
+// Account status of a user as the user endpoint reports it. These endpoints are outside of the service root,
+// they are always under /user of the client's URL.
+#[derive(RustcDecodable, Debug)]
+pub struct User {
+    username: String,
+    password_change_required: bool,
+}
+
+#[derive(RustcEncodable)]
+struct PasswordChange {
+    password: String,
+}
+
+impl User {
+    pub fn get(cli: &::client::Client, username: &str) -> Result<User, Error> {
+        let res = try_rest!(Self::get_request(cli, username), Ok);
+        Self::from_response(res)
+    }
+
+    fn get_request<'c>(cli: &'c ::client::Client, username: &str) -> RequestBuilder<'c> {
+        cli.get(user_path(username))
+    }
+
+    fn from_response(mut res: Response) -> Result<User, Error> {
+        let mut res_raw = String::new();
+        let _ = res.read_to_string(&mut res_raw);
+        match json::decode(&res_raw) {
+            Ok(user) => Ok(user),
+            Err(err) => Err(Error::from(err)),
+        }
+    }
+
+    // Works with the credentials expecting a password change. The client keeps sending the old password,
+    // a new client has to be built with the new one.
+    pub fn change_password(cli: &::client::Client, username: &str, password: String) -> Result<(), Error> {
+        let req = match Self::change_password_request(cli, username, password) {
+            Ok(req) => req,
+            Err(err) => return Err(err),
+        };
+        try_rest!(req, Ok);
+
+        info!("Password of {} changed", username);
+        Ok(())
+    }
+
+    fn change_password_request<'c>(cli: &'c ::client::Client, username: &str, password: String) -> Result<RequestBuilder<'c>, Error> {
+        let payload = match json::encode(&PasswordChange { password: password }) {
+            Ok(payload) => payload,
+            Err(err) => return Err(Error::from(err)),
+        };
+        Ok(cli.post(format!("{}/password", user_path(username))).body(&payload))
+    }
+
+    pub fn get_username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn is_password_change_required(&self) -> bool {
+        self.password_change_required
+    }
+}
+
+#[cfg(feature = "async")]
+impl User {
+    pub fn get_async(cli: &::client::Client, username: &str) -> BoxFuture<'static, Result<User, Error>> {
+        try_rest_async!(Self::get_request(cli, username), Ok)
+            .map(|res| res.and_then(Self::from_response))
+            .boxed()
+    }
+
+    pub fn change_password_async(cli: &::client::Client, username: &str, password: String) -> BoxFuture<'static, Result<(), Error>> {
+        let req = match Self::change_password_request(cli, username, password) {
+            Ok(req) => req,
+            Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        let username = username.to_string();
+        try_rest_async!(req, Ok)
+            .map_ok(move |_| info!("Password of {} changed", username))
+            .boxed()
+    }
+}
+
+fn user_path(username: &str) -> String {
+    format!("/user/{}", utf8_percent_encode(username, PATH_SEGMENT_ENCODE_SET))
+}
+
+#[cfg(test)]
+mod tests {
+    use client;
+    use node;
+    use user;
+    use hyper::method::Method;
+    use hyper::status::StatusCode;
+    use transport::MockTransport;
+    use types::Error;
+
+    const PASSWORD_CHANGE_REQUIRED: &str = r#"{"password_change":"http://localhost:7474/user/neo4j/password","errors":[{"code":"Neo.ClientError.Security.Forbidden","message":"User is required to change their password."}]}"#;
+
+    fn get_mock_client(transport: &MockTransport) -> ::client::Client {
+        let builder = client::ClientBuilder::new()
+            .discovery(false)
+            .credential("neo4j".to_string(), "neo4j".to_string())
+            .transport(transport.clone());
+        #[cfg(feature = "async")]
+        let builder = builder.async_transport(transport.clone());
+        builder.get()
+    }
+
+    #[test]
+    fn test_user_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Ok, r#"{"username":"neo4j","password_change":"http://localhost:7474/user/neo4j/password","password_change_required":true}"#);
+        transport.push_response(StatusCode::Ok, "");
+        let cli = get_mock_client(&transport);
+
+        let user = user::User::get(&cli, "neo4j").unwrap();
+        assert_eq!("neo4j", user.get_username());
+        assert!(user.is_password_change_required());
+        assert!(user::User::change_password(&cli, "neo4j", "s3cret\"".to_string()).is_ok());
+
+        let requests = transport.requests();
+        assert_eq!("http://localhost:7474/user/neo4j", requests[0].uri);
+        assert_eq!(Method::Post, requests[1].method);
+        assert_eq!("http://localhost:7474/user/neo4j/password", requests[1].uri);
+        assert_eq!(Some(r#"{"password":"s3cret\""}"#.to_string()), requests[1].body);
+    }
+
+    #[test]
+    fn test_password_change_required_with_mock() {
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Forbidden, PASSWORD_CHANGE_REQUIRED);
+        transport.push_response(StatusCode::Forbidden, PASSWORD_CHANGE_REQUIRED);
+        transport.push_response(StatusCode::Forbidden, "");
+        let cli = get_mock_client(&transport);
+
+        match cli.ping() {
+            Err(Error::PasswordChangeRequired(ref err)) => assert_eq!(StatusCode::Forbidden, err.status),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        match node::Node::<node::NodeUnidentifiedData>::get(&cli, 1) {
+            Err(err @ Error::PasswordChangeRequired(_)) => assert_eq!(Some(StatusCode::Forbidden), err.status()),
+            _ => panic!("Password change should be required"),
+        }
+        match cli.ping() {
+            Err(Error::ResponseError(_)) => {},
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_user_async_with_mock() {
+        use futures::executor::block_on;
+
+        let transport = MockTransport::new();
+        transport.push_response(StatusCode::Ok, r#"{"username":"admin user","password_change_required":false}"#);
+        transport.push_response(StatusCode::UnprocessableEntity, r#"{"errors":[{"code":"Neo.ClientError.General.InvalidArguments","message":"Old password and new password cannot be the same."}]}"#);
+        let cli = get_mock_client(&transport);
+
+        let user = block_on(user::User::get_async(&cli, "admin user")).unwrap();
+        assert!(!user.is_password_change_required());
+        let err = block_on(user::User::change_password_async(&cli, "admin user", "same".to_string())).unwrap_err();
+        assert_eq!(Some(StatusCode::UnprocessableEntity), err.status());
+        assert_eq!("http://localhost:7474/user/admin%20user", transport.requests()[0].uri);
+    }
+}